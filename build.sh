@@ -9,7 +9,8 @@ function generate_did() {
 
   cargo build --manifest-path="$canister_root/Cargo.toml" \
       --target wasm32-unknown-unknown \
-      --release --package "$canister"
+      --release --package "$canister" \
+      ${CARGO_FEATURES:+--features "$CARGO_FEATURES"}
 
   # See https://crates.io/crates/candid-extractor
   candid-extractor "target/wasm32-unknown-unknown/release/$canister.wasm" > "$canister_root/$canister.did"
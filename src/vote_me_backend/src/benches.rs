@@ -0,0 +1,81 @@
+use std::cell::RefCell;
+
+use candid::Principal;
+use ic_cdk::api::performance_counter;
+use ic_stable_structures::memory_manager::MemoryId;
+
+use crate::{
+    memory,
+    types::{Role, User, Users},
+};
+
+// Regions no subsystem uses, the roll is kept there between the calls that grow it
+const ROLL_USERS_MEMORY_ID: MemoryId = MemoryId::new(249);
+const ROLL_IDENTITIES_MEMORY_ID: MemoryId = MemoryId::new(250);
+const ROLL_STATS_MEMORY_ID: MemoryId = MemoryId::new(251);
+const ROLL_BATCHES_MEMORY_ID: MemoryId = MemoryId::new(252);
+const ROLL_PENDING_ACTIVATIONS_MEMORY_ID: MemoryId = MemoryId::new(253);
+const ROLL_DISTRICTS_MEMORY_ID: MemoryId = MemoryId::new(254);
+
+// Users added by one update call, a larger chunk would run out of the instruction limit
+const ROLL_CHUNK_SIZE: u64 = 5_000;
+
+thread_local! {
+    static ROLL: RefCell<Users> = RefCell::new(Users::init(
+        memory(ROLL_USERS_MEMORY_ID),
+        memory(ROLL_IDENTITIES_MEMORY_ID),
+        memory(ROLL_STATS_MEMORY_ID),
+        memory(ROLL_BATCHES_MEMORY_ID),
+        memory(ROLL_PENDING_ACTIVATIONS_MEMORY_ID),
+        memory(ROLL_DISTRICTS_MEMORY_ID),
+    ));
+}
+
+fn entry_identity(index: u64) -> Principal {
+    Principal::from_slice(&[&[0], &index.to_be_bytes()[..]].concat())
+}
+
+fn identity(index: u64) -> Principal {
+    Principal::from_slice(&[&[1], &index.to_be_bytes()[..]].concat())
+}
+
+// Grows the activated roll towards `count` users by one chunk, every tenth user belongs to the committee.
+// Returns the size of the roll, the caller repeats until it reaches `count`
+#[ic_cdk::update]
+fn benchmark_grow_roll(count: u64) -> u64 {
+    ROLL.with(|users| {
+        let mut users = users.borrow_mut();
+        let from = users.len() as u64;
+
+        for index in from..count.min(from + ROLL_CHUNK_SIZE) {
+            let role = if index % 10 == 0 {
+                Role::Committee
+            } else {
+                Role::User
+            };
+            let mut user = User::new_with_role(&entry_identity(index), role);
+            user.activate(identity(index), "seed".to_string());
+            users.push(user);
+        }
+
+        users.len() as u64
+    })
+}
+
+// Instructions spent on the lookups done by every committee guard and vote, for the roll grown so far
+#[ic_cdk::query]
+fn benchmark_users_lookups() -> u64 {
+    ROLL.with(|users| {
+        let users = users.borrow();
+        let last = (users.len() as u64).saturating_sub(1);
+
+        let start = performance_counter(0);
+        let _ = users.is_in_committee(identity(last));
+        let _ = users.get_voting_weight(identity(last));
+        let _ = users.get_seed_by_entry_identity(entry_identity(last));
+        users.get_committee_size();
+        users.get_electorate(None);
+
+        performance_counter(0) - start
+    })
+}
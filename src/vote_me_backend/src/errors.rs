@@ -1,43 +1,185 @@
+use candid::{CandidType, Deserialize, Principal};
 use thiserror::Error;
 
-#[derive(Error, Debug, PartialEq)]
+use crate::types::{DelegationTopic, UserStatus, VoteState};
+
+#[derive(Error, CandidType, Deserialize, Clone, Debug, PartialEq)]
 pub enum ContractError {
     #[error("Anonymous principal not allowed to make calls.")]
     AnonymousCaller,
 
-    #[error("User not found.")]
-    UserNotFound,
+    #[error("User {principal} not found.")]
+    UserNotFound { principal: Principal },
+
+    #[error("Entry identity {entry_identity} is already registered.")]
+    EntryIdentityAlreadyRegistered { entry_identity: Principal },
+
+    #[error("User with entry identity {entry_identity} is not activated.")]
+    UserNotActivated { entry_identity: Principal },
+
+    #[error("User with entry identity {entry_identity} is not active, user is {status:?}.")]
+    UserNotActive {
+        entry_identity: Principal,
+        status: UserStatus,
+    },
+
+    #[error("District {district_id} not found.")]
+    DistrictNotFound { district_id: u64 },
+
+    #[error("Committee of district {district_id:?} is full, it has {max_committee_size} members.")]
+    CommitteeFull {
+        district_id: Option<u64>,
+        max_committee_size: u64,
+    },
+
+    #[error("Propose {id} is open only to users of district {district_id}.")]
+    NotInDistrict { id: usize, district_id: u64 },
+
+    #[error("Results of presidential elections {id} are not computed until the propose closes.")]
+    ResultsNotAvailable { id: usize },
+
+    #[error("User {principal} is not in the committee of district {district_id:?}.")]
+    NotInDistrictCommittee {
+        district_id: Option<u64>,
+        principal: Principal,
+    },
+
+    #[error("User {principal} is not in the committee certifying results of district {district_id:?} in propose {id}.")]
+    NotInCertifyingCommittee {
+        id: usize,
+        district_id: Option<u64>,
+        principal: Principal,
+    },
+
+    #[error("User {principal} has already certified results of district {district_id:?} in propose {id}.")]
+    ResultsAlreadyCertified {
+        id: usize,
+        district_id: Option<u64>,
+        principal: Principal,
+    },
+
+    #[error("Registration batch {batch_id} with voter roll not found.")]
+    RegistrationBatchNotFound { batch_id: u64 },
+
+    #[error("Inclusion proof of entry identity {entry_identity} does not match the voter roll.")]
+    InvalidInclusionProof { entry_identity: Principal },
+
+    #[error("Activation deadline {deadline} of entry identity {entry_identity} has passed.")]
+    ActivationDeadlinePassed {
+        entry_identity: Principal,
+        deadline: u64,
+    },
+
+    #[error("User with entry identity {entry_identity} is already activated.")]
+    UserAlreadyActivated { entry_identity: Principal },
+
+    #[error("Identity {identity} is already bound to another user.")]
+    IdentityAlreadyBound { identity: Principal },
+
+    #[error("User with entry identity {entry_identity} can activate a new identity after {available_at}.")]
+    IdentityResetCooldown {
+        entry_identity: Principal,
+        available_at: u64,
+    },
+
+    #[error("User {principal} do not belongs to committee.")]
+    NotInCommittee { principal: Principal },
 
-    #[error("User do not belongs to committee.")]
-    NotInCommittee,
+    #[error("User {principal} is neither in the committee nor an observer.")]
+    NotObserver { principal: Principal },
+
+    #[error("Observer {principal} can not vote.")]
+    ObserverCannotVote { principal: Principal },
+
+    #[error("Committee member {principal} can not be appointed an observer, demote them first.")]
+    CommitteeMemberCannotObserve { principal: Principal },
 
     #[error("Config is not set.")]
     ConfigNotSet,
 
-    #[error("Propose not found.")]
-    ProposeNotFound,
+    #[error("Propose {id} not found.")]
+    ProposeNotFound { id: usize },
+
+    #[error("Vote on propose {id} is not open, propose is {state:?}.")]
+    VoteNotOpen { id: usize, state: VoteState },
 
-    #[error("Vote is not open.")]
-    VoteNotOpen,
+    #[error("User {principal} already voted on propose {id}.")]
+    UserAlreadyVoted { id: usize, principal: Principal },
 
-    #[error("User already voted.")]
-    UserAlreadyVoted,
+    #[error("User {principal} has not voted on propose {id}.")]
+    UserNotVoted { id: usize, principal: Principal },
+
+    #[error("Revoting is not allowed.")]
+    RevotingNotAllowed,
 
     #[error("Invalid action.")]
     InvalidAction,
 
-    #[error("Propose is still in progress.")]
-    ProposeInProgress,
+    #[error("Propose {id} is still in progress until {closes_at}.")]
+    ProposeInProgress { id: usize, closes_at: u64 },
 
     #[error("The canister is already initialized.")]
     AlreadyInitialized,
 
-    #[error("Threshold is lower then min threshold length (committee_size / 2) + 1.")]
-    ThresholdToLow,
+    #[error("Threshold {threshold} is lower then min threshold length (committee_size / 2) + 1.")]
+    ThresholdToLow { threshold: u16 },
+
+    #[error("Candidate {index} do not found, propose has {candidates_count} candidates.")]
+    CandidatesNotFound {
+        index: usize,
+        candidates_count: usize,
+    },
+
+    #[error("Invalid percentage {percentage}.")]
+    InvalidPercentage { percentage: u16 },
+
+    #[error("Ballot has {expected} questions but {actual} answers were given.")]
+    InvalidBallotAnswers { expected: usize, actual: usize },
+
+    #[error("Answer to ballot question {question} does not match the question.")]
+    InvalidBallotAnswer { question: usize },
+
+    #[error("Tie break of propose {id} is not pending.")]
+    TieBreakNotPending { id: usize },
+
+    #[error("Candidate {candidate} is not tied.")]
+    CandidateNotTied { candidate: usize },
+
+    #[error(
+        "Chosen candidates do not fill the tie break seats, expected {expected} but got {actual}."
+    )]
+    InvalidTieBreakChoice { expected: usize, actual: usize },
+
+    #[error("Delegation to {delegate} would create a cycle.")]
+    DelegationCycle { delegate: Principal },
+
+    #[error("Event chain is broken at event {sequence}.")]
+    EventChainBroken { sequence: u64 },
+
+    #[error("Delegation for topic {topic:?} not found.")]
+    DelegationNotFound { topic: DelegationTopic },
+
+    #[error("Announced elections {id} not found.")]
+    AnnouncementNotFound { id: usize },
+
+    #[error("Candidate registration of announced elections {id} is closed.")]
+    CandidateRegistrationClosed { id: usize },
+
+    #[error("User {principal} has already declared candidacy in announced elections {id}.")]
+    AlreadyCandidate { id: usize, principal: Principal },
+
+    #[error("User {candidate} is not a candidate in announced elections {id}.")]
+    CandidateNotFound { id: usize, candidate: Principal },
 
-    #[error("Candidates do not found.")]
-    CandidatesNotFound,
+    #[error("User {principal} has already signed the nomination of {candidate} in announced elections {id}.")]
+    NominationAlreadySigned {
+        id: usize,
+        candidate: Principal,
+        principal: Principal,
+    },
 
-    #[error("Invalid percentage.")]
-    InvalidPercentage,
+    #[error(
+        "Candidate {candidate} can not sign their own nomination in announced elections {id}."
+    )]
+    OwnNominationSigned { id: usize, candidate: Principal },
 }
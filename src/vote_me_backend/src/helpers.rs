@@ -2,12 +2,28 @@ use candid::Principal;
 
 use crate::errors::ContractError;
 
-pub fn caller() -> Result<Principal, String> {
+pub fn caller() -> Result<Principal, ContractError> {
     let caller = ic_cdk::caller();
     // The anonymous principal is not allowed to interact with the
     // encrypted notes canister.
     if caller == Principal::anonymous() {
-        return Err(ContractError::AnonymousCaller.to_string());
+        return Err(ContractError::AnonymousCaller);
     }
     Ok(caller)
 }
+
+// Time left until `time`, zero once it has passed
+pub fn time_until(time: u64) -> std::time::Duration {
+    std::time::Duration::from_nanos(time.saturating_sub(ic_cdk::api::time()))
+}
+
+// Upgrades drop every timer, items carried over that are still pending get theirs back, due ones fire at once
+pub fn rearm_timers<T>(
+    items: &[T],
+    pending: impl Fn(&T) -> Option<(usize, u64)>,
+    on_deadline: fn(usize),
+) {
+    for (id, deadline) in items.iter().filter_map(pending) {
+        ic_cdk_timers::set_timer(time_until(deadline), move || on_deadline(id));
+    }
+}
@@ -1,32 +1,81 @@
-use std::cell::RefCell;
+use std::{cell::RefCell, collections::BTreeSet};
 
 use candid::Principal;
 use errors::ContractError;
-use ic_cdk::println;
+use ic_cdk::{api::management_canister::main::raw_rand, println};
+use ic_stable_structures::{
+    memory_manager::{MemoryId, MemoryManager},
+    DefaultMemoryImpl, StableCell,
+};
 use types::{
-    CommitteeProposeCandidType, PresidentialElectionsProposeCandidType, UserPropose,
-    UserProposeVote,
-    Users
+    BallotProposals, BallotProposeCandidType, CommitteeProposeCandidType, DelegationEdge,
+    DelegationTopic, Delegations, DistrictCandidType, ElectionsAnnouncementCandidType,
+    ElectionsAnnouncements, Event, EventKind, Events, EventsHead, Memory, Page, PendingProposals,
+    PresidentialElectionsProposeCandidType, ProposeFilter, ProposeKind,
+    RegistrationBatchCandidType, ResultsNode, Turnout, UpgradeState, UserPropose, UserProposeVote,
+    UserStatus, Users,
 };
 
 use crate::{
-    helpers::caller,
+    helpers::{caller, time_until},
     types::{
         CommitteeActions, CommitteeProposals, Config, PresidentialElectionsProposals, Role, User,
     },
 };
 
+#[cfg(feature = "benchmark")]
+mod benches;
 mod errors;
 mod helpers;
 mod types;
 
+// Every subsystem kept in stable memory owns its own memory-manager regions
+const EVENTS_INDEX_MEMORY_ID: MemoryId = MemoryId::new(0);
+const EVENTS_DATA_MEMORY_ID: MemoryId = MemoryId::new(1);
+const USERS_MEMORY_ID: MemoryId = MemoryId::new(2);
+const USERS_IDENTITIES_MEMORY_ID: MemoryId = MemoryId::new(3);
+const USERS_STATS_MEMORY_ID: MemoryId = MemoryId::new(4);
+const COMMITTEE_VOTERS_MEMORY_ID: MemoryId = MemoryId::new(5);
+const PRESIDENTIAL_ELECTIONS_BALLOTS_MEMORY_ID: MemoryId = MemoryId::new(6);
+const BALLOTS_BALLOTS_MEMORY_ID: MemoryId = MemoryId::new(7);
+const USERS_BATCHES_MEMORY_ID: MemoryId = MemoryId::new(8);
+const USERS_PENDING_ACTIVATIONS_MEMORY_ID: MemoryId = MemoryId::new(9);
+const USERS_DISTRICTS_MEMORY_ID: MemoryId = MemoryId::new(10);
+const UPGRADE_STATE_MEMORY_ID: MemoryId = MemoryId::new(11);
+
+// Wait before asking the management canister for randomness again
+const LOT_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(10);
+
 thread_local! {
-    static CONFIG: RefCell<Option<Config>> = RefCell::new(None);
-    static USERS: RefCell<Users> = RefCell::new(Users::default());
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> =
+        RefCell::new(MemoryManager::init(DefaultMemoryImpl::default()));
+    static EVENTS: RefCell<Events> = RefCell::new(Events::init(
+        memory(EVENTS_INDEX_MEMORY_ID),
+        memory(EVENTS_DATA_MEMORY_ID),
+    ));
+    static CONFIG: RefCell<Option<Config>> = const { RefCell::new(None) };
+    static USERS: RefCell<Users> = RefCell::new(Users::init(
+        memory(USERS_MEMORY_ID),
+        memory(USERS_IDENTITIES_MEMORY_ID),
+        memory(USERS_STATS_MEMORY_ID),
+        memory(USERS_BATCHES_MEMORY_ID),
+        memory(USERS_PENDING_ACTIVATIONS_MEMORY_ID),
+        memory(USERS_DISTRICTS_MEMORY_ID),
+    ));
     static COMMITTEE_PROPOSALS: RefCell<CommitteeProposals> =
-        RefCell::new(CommitteeProposals::default());
-    static PRESIDENTIAL_ELECTIONS: RefCell<PresidentialElectionsProposals> =
-        RefCell::new(PresidentialElectionsProposals::default());
+        RefCell::new(CommitteeProposals::init(memory(COMMITTEE_VOTERS_MEMORY_ID)));
+    static PRESIDENTIAL_ELECTIONS: RefCell<PresidentialElectionsProposals> = RefCell::new(
+        PresidentialElectionsProposals::init(memory(PRESIDENTIAL_ELECTIONS_BALLOTS_MEMORY_ID)),
+    );
+    static BALLOTS: RefCell<BallotProposals> =
+        RefCell::new(BallotProposals::init(memory(BALLOTS_BALLOTS_MEMORY_ID)));
+    static DELEGATIONS: RefCell<Delegations> = RefCell::new(Delegations::default());
+    static ANNOUNCEMENTS: RefCell<ElectionsAnnouncements> =
+        RefCell::new(ElectionsAnnouncements::default());
+}
+
+pub(crate) fn memory(id: MemoryId) -> Memory {
+    MEMORY_MANAGER.with(|memory_manager| memory_manager.borrow().get(id))
 }
 
 #[ic_cdk::init]
@@ -35,23 +84,32 @@ fn init(config: Config, entry_identities: Vec<Principal>) {
 
     assert!(users_count == 0, "{}", ContractError::AlreadyInitialized);
     assert!(
-        config.committee_threshold <= 100_00,
+        config.committee_threshold <= 10_000,
         "{}",
-        ContractError::ThresholdToLow
+        ContractError::ThresholdToLow {
+            threshold: config.committee_threshold
+        }
     );
     assert!(
-        config.presidential_elections_threshold <= 100_00,
+        config.presidential_elections_threshold <= 10_000,
         "{}",
-        ContractError::InvalidPercentage
+        ContractError::InvalidPercentage {
+            percentage: config.presidential_elections_threshold
+        }
     );
 
+    record_event(EventKind::ConfigChanged(config.redacted()));
     CONFIG.with(|config_ref| *config_ref.borrow_mut() = Some(config));
 
     USERS.with(|users| {
         entry_identities.iter().for_each(|entry_identity| {
             users
                 .borrow_mut()
-                .push(User::new_with_role(entry_identity, Role::Committee))
+                .push(User::new_with_role(entry_identity, Role::Committee));
+            record_event(EventKind::UserRegistered {
+                entry_identity: *entry_identity,
+                role: Role::Committee,
+            });
         })
     });
 
@@ -64,75 +122,512 @@ fn init(config: Config, entry_identities: Vec<Principal>) {
     );
 }
 
+fn upgrade_state_cell() -> StableCell<UpgradeState, Memory> {
+    StableCell::init(memory(UPGRADE_STATE_MEMORY_ID), UpgradeState::default())
+        .expect("Failed to initialize the upgrade state.")
+}
+
+// Heap state is written to stable memory, timers do not survive an upgrade and are armed again from it
+#[ic_cdk::pre_upgrade]
+fn pre_upgrade() {
+    let state = UpgradeState {
+        config: CONFIG.with(|config| config.borrow_mut().take()),
+        committee_proposals: COMMITTEE_PROPOSALS
+            .with(|committee_proposals| committee_proposals.borrow_mut().take_proposals()),
+        presidential_elections: PRESIDENTIAL_ELECTIONS
+            .with(|presidential_elections| presidential_elections.borrow_mut().take_proposals()),
+        ballots: BALLOTS.with(|ballots| ballots.borrow_mut().take_proposals()),
+        delegations: DELEGATIONS.with(|delegations| {
+            std::mem::replace(&mut *delegations.borrow_mut(), Delegations::default())
+        }),
+        announcements: ANNOUNCEMENTS
+            .with(|announcements| announcements.borrow_mut().take_announcements()),
+    };
+
+    upgrade_state_cell()
+        .set(state)
+        .expect("Failed to save the upgrade state.");
+}
+
+// Events live in stable memory, so their head is certified again after an upgrade, then the heap state is read back
+#[ic_cdk::post_upgrade]
+fn post_upgrade() {
+    let head_hash = EVENTS.with(|events| events.borrow().head_hash());
+    ic_cdk::api::set_certified_data(&head_hash);
+
+    // The cell is emptied so the state is not kept twice
+    let state = upgrade_state_cell()
+        .set(UpgradeState::default())
+        .expect("Failed to load the upgrade state.");
+    let config = state.config.expect("Upgrade state without config.");
+
+    COMMITTEE_PROPOSALS.with(|committee_proposals| {
+        committee_proposals
+            .borrow_mut()
+            .restore_proposals(&config, state.committee_proposals)
+    });
+    PRESIDENTIAL_ELECTIONS.with(|presidential_elections| {
+        presidential_elections
+            .borrow_mut()
+            .restore_proposals(&config, state.presidential_elections)
+    });
+    for id in PRESIDENTIAL_ELECTIONS
+        .with(|presidential_elections| presidential_elections.borrow().get_awaiting_lot())
+    {
+        schedule_presidential_elections_lot(id, std::time::Duration::ZERO);
+    }
+    BALLOTS.with(|ballots| {
+        ballots
+            .borrow_mut()
+            .restore_proposals(&config, state.ballots)
+    });
+    DELEGATIONS.with(|delegations| *delegations.borrow_mut() = state.delegations);
+    ANNOUNCEMENTS.with(|announcements| {
+        announcements
+            .borrow_mut()
+            .restore_announcements(&config, state.announcements)
+    });
+    for (batch_id, activation_deadline) in USERS.with(|users| users.borrow().get_expiring_batches())
+    {
+        ic_cdk_timers::set_timer(time_until(activation_deadline), move || {
+            expire_registration_batch(batch_id)
+        });
+    }
+    CONFIG.with(|config_ref| *config_ref.borrow_mut() = Some(config));
+}
+
 // Committee actions
 
-#[ic_cdk::update(guard = "committee_guard")]
-fn committee_create_propose(_propose: CommitteeActions) -> usize {
-    let caller = caller().unwrap();
-    let propose = _propose.validate().unwrap();
+#[ic_cdk::update]
+fn committee_create_propose(_propose: CommitteeActions) -> Result<usize, ContractError> {
+    committee_guard()?;
+    let propose = _propose.validate()?;
+    let district_id = committee_scope(&propose)?;
+    let caller = district_committee_guard(district_id)?;
 
     let config = CONFIG
         .with(|config| config.borrow().clone())
-        .ok_or(ContractError::ConfigNotSet)
-        .unwrap();
+        .ok_or(ContractError::ConfigNotSet)?;
 
-    COMMITTEE_PROPOSALS.with(|committee_proposals| {
+    Ok(COMMITTEE_PROPOSALS.with(|committee_proposals| {
         committee_proposals
             .borrow_mut()
-            .create_proposal(config, caller, propose)
-    })
+            .create_proposal(config, caller, propose, district_id)
+    }))
 }
 
-#[ic_cdk::update(guard = "committee_guard")]
-fn committee_vote_on_propose(propose_id: usize) {
-    let caller = caller().unwrap();
+#[ic_cdk::update]
+fn committee_vote_on_propose(propose_id: usize) -> Result<(), ContractError> {
+    committee_guard()?;
+    let district_id = COMMITTEE_PROPOSALS
+        .with(|committee_proposals| committee_proposals.borrow().get_district_id(propose_id))?;
+    let caller = district_committee_guard(district_id)?;
+    let voter = USERS.with(|users| users.borrow().get_voter(caller))?;
 
     COMMITTEE_PROPOSALS
-        .with(|committee_proposals| committee_proposals.borrow_mut().vote(caller, propose_id))
-        .unwrap();
+        .with(|committee_proposals| committee_proposals.borrow_mut().vote(voter, propose_id))
+}
+
+#[ic_cdk::update]
+fn committee_retract_vote(propose_id: usize) -> Result<(), ContractError> {
+    committee_guard()?;
+    let district_id = COMMITTEE_PROPOSALS
+        .with(|committee_proposals| committee_proposals.borrow().get_district_id(propose_id))?;
+    let caller = district_committee_guard(district_id)?;
+    let voter = USERS.with(|users| users.borrow().get_voter(caller))?;
+    let config = CONFIG
+        .with(|config| config.borrow().clone())
+        .ok_or(ContractError::ConfigNotSet)?;
+
+    COMMITTEE_PROPOSALS.with(|committee_proposals| {
+        committee_proposals
+            .borrow_mut()
+            .retract_vote(voter, propose_id, config.allow_revoting)
+    })
 }
 
 // User actions
 
 #[ic_cdk::update]
-fn activate_user(identity: Principal, identity_seed: String) {
-    let caller = caller().unwrap();
+fn activate_user(identity: Principal, identity_seed: String) -> Result<(), ContractError> {
+    let caller = caller()?;
+    let config = CONFIG
+        .with(|config| config.borrow().clone())
+        .ok_or(ContractError::ConfigNotSet)?;
+    let entry_identity = config.registered_entry_identity(caller);
 
-    USERS
-        .with(|users| {
-            users
+    let handed_over = USERS.with(|users| {
+        users.borrow_mut().activate_user(
+            entry_identity,
+            identity,
+            identity_seed,
+            config.identity_reset_cooldown,
+        )
+    })?;
+    if let Some(previous_identity) = handed_over {
+        DELEGATIONS.with(|delegations| {
+            delegations
                 .borrow_mut()
-                .activate_user(caller, identity, identity_seed)
-        })
-        .unwrap()
+                .transfer(previous_identity, identity)
+        });
+    }
+
+    Ok(())
 }
 
+// Registers the caller from an approved voter roll and activates them in one call
 #[ic_cdk::update]
-fn vote_on_propose(propose: UserProposeVote, propose_id: usize) {
-    let caller = caller().unwrap();
+fn activate_user_with_proof(
+    batch_id: u64,
+    proof: Vec<Vec<u8>>,
+    identity: Principal,
+    identity_seed: String,
+) -> Result<(), ContractError> {
+    let caller = caller()?;
+    let config = CONFIG
+        .with(|config| config.borrow().clone())
+        .ok_or(ContractError::ConfigNotSet)?;
+    let entry_identity = config.registered_entry_identity(caller);
+
+    USERS.with(|users| {
+        let mut users = users.borrow_mut();
+        // Identity is checked up front so a rejected activation leaves no registration behind
+        if users.contains_identity(identity) {
+            return Err(ContractError::IdentityAlreadyBound { identity });
+        }
+        users.register_with_proof(entry_identity, batch_id, &proof)?;
+        record_event(EventKind::UserRegistered {
+            entry_identity,
+            role: Role::User,
+        });
+        users
+            .activate_user(
+                entry_identity,
+                identity,
+                identity_seed,
+                config.identity_reset_cooldown,
+            )
+            .map(|_| ())
+    })
+}
+
+// Called by the currently active identity to let the entry identity activate `new_identity` instead
+#[ic_cdk::update]
+fn approve_identity_change(new_identity: Principal) -> Result<(), ContractError> {
+    let caller = caller()?;
+    let entry_identity = USERS.with(|users| {
+        users
+            .borrow_mut()
+            .approve_identity_change(caller, new_identity)
+    })?;
+    record_event(EventKind::IdentityChangeApproved {
+        entry_identity,
+        identity: caller,
+        new_identity,
+    });
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn vote_on_propose(propose: UserProposeVote, propose_id: usize) -> Result<(), ContractError> {
+    let caller = caller()?;
+    let config = CONFIG
+        .with(|config| config.borrow().clone())
+        .ok_or(ContractError::ConfigNotSet)?;
+    let (voter, voting_weight, district_id, district_parents) = USERS.with(|users| {
+        let users = users.borrow();
+        Ok::<_, ContractError>((
+            users.get_voter(caller)?,
+            users.get_voting_weight(caller)?,
+            users.get_district_id(caller)?,
+            users.get_district_parents(),
+        ))
+    })?;
 
     match propose {
-        UserProposeVote::PresidentialElections(candidate_index) => PRESIDENTIAL_ELECTIONS
-            .with(|committee_proposals| {
-                committee_proposals
-                    .borrow_mut()
-                    .vote(caller, propose_id, &candidate_index)
+        UserProposeVote::PresidentialElections(candidate_index) => {
+            PRESIDENTIAL_ELECTIONS.with(|committee_proposals| {
+                committee_proposals.borrow_mut().vote(
+                    voter,
+                    propose_id,
+                    &candidate_index,
+                    voting_weight,
+                    (district_id, &district_parents),
+                    config.allow_revoting,
+                )
             })
-            .unwrap(),
+        }
+        UserProposeVote::Ballot(answers) => BALLOTS.with(|ballots| {
+            ballots.borrow_mut().vote(
+                voter,
+                propose_id,
+                &answers,
+                voting_weight,
+                config.allow_revoting,
+            )
+        }),
     }
 }
 
+#[ic_cdk::update]
+fn declare_candidacy(announcement_id: usize, name: String) -> Result<(), ContractError> {
+    let caller = caller()?;
+    if name.is_empty() {
+        return Err(ContractError::InvalidAction);
+    }
+    let district_id = eligible_voter_district(caller)?;
+
+    ANNOUNCEMENTS.with(|announcements| {
+        announcements.borrow_mut().declare_candidacy(
+            announcement_id,
+            caller,
+            name.clone(),
+            district_id,
+        )
+    })?;
+    record_event(EventKind::CandidacyDeclared {
+        id: announcement_id,
+        candidate: caller,
+        name,
+    });
+
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn sign_nomination(announcement_id: usize, candidate: Principal) -> Result<(), ContractError> {
+    let caller = caller()?;
+    let district_id = eligible_voter_district(caller)?;
+
+    ANNOUNCEMENTS.with(|announcements| {
+        announcements
+            .borrow_mut()
+            .sign_nomination(announcement_id, candidate, caller, district_id)
+    })?;
+    record_event(EventKind::NominationSigned {
+        id: announcement_id,
+        candidate,
+        signer: caller,
+    });
+
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn delegate_vote(topic: DelegationTopic, delegate: Principal) -> Result<(), ContractError> {
+    let caller = caller()?;
+
+    USERS.with(|users| {
+        let users = users.borrow();
+        match [caller, delegate]
+            .into_iter()
+            .find(|principal| !users.contains_identity(*principal))
+        {
+            Some(principal) => Err(ContractError::UserNotFound { principal }),
+            None => Ok(()),
+        }
+    })?;
+
+    DELEGATIONS.with(|delegations| delegations.borrow_mut().delegate(caller, topic, delegate))?;
+    record_event(EventKind::VoteDelegated {
+        delegator: caller,
+        topic,
+        delegate,
+    });
+
+    Ok(())
+}
+
+#[ic_cdk::update]
+fn revoke_delegation(topic: DelegationTopic) -> Result<(), ContractError> {
+    let caller = caller()?;
+
+    DELEGATIONS.with(|delegations| delegations.borrow_mut().revoke(caller, topic))?;
+    record_event(EventKind::DelegationRevoked {
+        delegator: caller,
+        topic,
+    });
+
+    Ok(())
+}
+
+#[ic_cdk::query]
+fn get_announced_elections() -> Vec<ElectionsAnnouncementCandidType> {
+    ANNOUNCEMENTS.with(|announcements| announcements.borrow().get())
+}
+
 #[ic_cdk::query]
 fn get_presidential_elections() -> Vec<PresidentialElectionsProposeCandidType> {
     PRESIDENTIAL_ELECTIONS.with(|proposals| proposals.borrow().get().clone())
 }
 
 #[ic_cdk::query]
-fn get_salt() -> String {
-    let entry_identity = caller().unwrap();
-    USERS
-        .with(|users| users.borrow().get_seed_by_entry_identity(entry_identity))
-        .unwrap()
+fn get_presidential_election(
+    id: usize,
+) -> Result<PresidentialElectionsProposeCandidType, ContractError> {
+    PRESIDENTIAL_ELECTIONS.with(|proposals| proposals.borrow().get_by_id(id))
+}
+
+#[ic_cdk::query]
+fn list_presidential_elections(
+    filter: ProposeFilter,
+    cursor: Option<usize>,
+    limit: usize,
+) -> Page<PresidentialElectionsProposeCandidType> {
+    PRESIDENTIAL_ELECTIONS.with(|proposals| proposals.borrow().get_page(&filter, cursor, limit))
+}
+
+#[ic_cdk::query]
+fn get_presidential_elections_history(
+    id: usize,
+) -> Result<Vec<PresidentialElectionsProposeCandidType>, ContractError> {
+    PRESIDENTIAL_ELECTIONS.with(|proposals| proposals.borrow().get_history(id))
+}
+
+// Totals of every district level, available once the propose closes
+#[ic_cdk::query]
+fn get_presidential_elections_results(id: usize) -> Result<ResultsNode, ContractError> {
+    PRESIDENTIAL_ELECTIONS.with(|proposals| proposals.borrow().get_results(id))
+}
+
+// Committee member certifies the partial results of their own district, or the national ones outside districts
+#[ic_cdk::update]
+fn certify_presidential_elections_results(id: usize) -> Result<(), ContractError> {
+    let caller = committee_guard()?;
+    let config = CONFIG
+        .with(|config| config.borrow().clone())
+        .ok_or(ContractError::ConfigNotSet)?;
+    let level = USERS.with(|users| {
+        let users = users.borrow();
+        let district_id = users.get_district_id(caller)?;
+        Ok::<_, ContractError>((district_id, users.get_level_committee_size(district_id)?))
+    })?;
+
+    PRESIDENTIAL_ELECTIONS.with(|proposals| {
+        proposals
+            .borrow_mut()
+            .certify_results(&config, id, level, caller)
+    })
+}
+
+#[ic_cdk::query]
+fn get_presidential_elections_delegation_graph(
+    id: usize,
+) -> Result<Vec<DelegationEdge>, ContractError> {
+    let voters = voter_identities(
+        &PRESIDENTIAL_ELECTIONS.with(|proposals| proposals.borrow().get_voters(id))?,
+    );
+
+    Ok(DELEGATIONS.with(|delegations| {
+        delegations
+            .borrow()
+            .resolve(DelegationTopic::PresidentialElections, &voters)
+    }))
+}
+
+#[ic_cdk::query]
+fn get_ballots() -> Vec<BallotProposeCandidType> {
+    BALLOTS.with(|ballots| ballots.borrow().get())
+}
+
+#[ic_cdk::query]
+fn get_ballot_delegation_graph(id: usize) -> Result<Vec<DelegationEdge>, ContractError> {
+    let voters = voter_identities(&BALLOTS.with(|ballots| ballots.borrow().get_voters(id))?);
+
+    Ok(DELEGATIONS.with(|delegations| {
+        delegations
+            .borrow()
+            .resolve(DelegationTopic::Ballot, &voters)
+    }))
+}
+
+#[ic_cdk::query]
+fn has_voted(kind: ProposeKind, id: usize) -> Result<bool, ContractError> {
+    let caller = caller()?;
+    let entry_identity = USERS
+        .with(|users| users.borrow().get_voter(caller))?
+        .entry_identity;
+
+    match kind {
+        ProposeKind::Committee => COMMITTEE_PROPOSALS
+            .with(|committee_proposals| committee_proposals.borrow().has_voted(id, entry_identity)),
+        ProposeKind::PresidentialElections => PRESIDENTIAL_ELECTIONS
+            .with(|proposals| proposals.borrow().has_voted(id, entry_identity)),
+        ProposeKind::Ballot => {
+            BALLOTS.with(|ballots| ballots.borrow().has_voted(id, entry_identity))
+        }
+    }
+}
+
+#[ic_cdk::query]
+fn get_salt() -> Result<String, ContractError> {
+    let caller = caller()?;
+    let entry_identity = CONFIG
+        .with(|config| config.borrow().clone())
+        .ok_or(ContractError::ConfigNotSet)?
+        .registered_entry_identity(caller);
+
+    USERS.with(|users| users.borrow().get_seed_by_entry_identity(entry_identity))
+}
+
+// Committee needs the salt to hash entry identities before registering them
+#[ic_cdk::query]
+fn get_entry_identity_salt() -> Result<Option<Vec<u8>>, ContractError> {
+    committee_guard()?;
+
+    Ok(CONFIG
+        .with(|config| config.borrow().clone())
+        .ok_or(ContractError::ConfigNotSet)?
+        .entry_identity_salt)
+}
+
+#[ic_cdk::query]
+fn get_districts() -> Vec<DistrictCandidType> {
+    USERS.with(|users| users.borrow().get_districts())
+}
+
+#[ic_cdk::query]
+fn get_registration_batches() -> Result<Vec<RegistrationBatchCandidType>, ContractError> {
+    committee_guard()?;
+
+    Ok(USERS.with(|users| users.borrow().get_batches()))
+}
+
+// Audit log is privileged, anyone can still check the certified head
+#[ic_cdk::query]
+fn get_events(from: u64, limit: u64) -> Result<Vec<Event>, ContractError> {
+    observer_guard()?;
+
+    Ok(EVENTS.with(|events| events.borrow().get(from, limit)))
+}
+
+#[ic_cdk::query]
+fn get_events_head() -> EventsHead {
+    EVENTS.with(|events| {
+        let events = events.borrow();
+        EventsHead {
+            hash: events.head_hash(),
+            count: events.len(),
+            certificate: ic_cdk::api::data_certificate(),
+        }
+    })
+}
+
+// Verifies the log a page at a time, each call continues from the hash and count returned by the previous one
+// until the count reaches the certified head
+#[ic_cdk::query]
+fn verify_event_log(
+    from: u64,
+    previous_hash: Vec<u8>,
+    limit: u64,
+) -> Result<EventsHead, ContractError> {
+    let (hash, count) = EVENTS.with(|events| events.borrow().verify(from, previous_hash, limit))?;
+
+    Ok(EventsHead {
+        hash,
+        count,
+        certificate: ic_cdk::api::data_certificate(),
+    })
 }
 
 #[ic_cdk::query]
@@ -140,6 +635,88 @@ fn get_committee_proposals() -> Vec<CommitteeProposeCandidType> {
     COMMITTEE_PROPOSALS.with(|committee_proposals| committee_proposals.borrow().get().clone())
 }
 
+#[ic_cdk::query]
+fn get_committee_proposal(id: usize) -> Result<CommitteeProposeCandidType, ContractError> {
+    COMMITTEE_PROPOSALS.with(|committee_proposals| committee_proposals.borrow().get_by_id(id))
+}
+
+#[ic_cdk::query]
+fn list_committee_proposals(
+    filter: ProposeFilter,
+    cursor: Option<usize>,
+    limit: usize,
+) -> Page<CommitteeProposeCandidType> {
+    COMMITTEE_PROPOSALS.with(|committee_proposals| {
+        committee_proposals
+            .borrow()
+            .get_page(&filter, cursor, limit)
+    })
+}
+
+#[ic_cdk::query]
+fn get_committee_proposal_voters(id: usize) -> Result<Vec<Principal>, ContractError> {
+    observer_guard()?;
+
+    Ok(voter_identities(&COMMITTEE_PROPOSALS.with(
+        |committee_proposals| committee_proposals.borrow().get_voters(id),
+    )?))
+}
+
+#[ic_cdk::query]
+fn get_pending_proposals() -> Result<PendingProposals, ContractError> {
+    observer_guard()?;
+
+    Ok(PendingProposals {
+        committee: COMMITTEE_PROPOSALS
+            .with(|committee_proposals| committee_proposals.borrow().get_open()),
+        presidential_elections: PRESIDENTIAL_ELECTIONS
+            .with(|proposals| proposals.borrow().get_open()),
+        ballots: BALLOTS.with(|ballots| ballots.borrow().get_open()),
+    })
+}
+
+// Turnout while the propose is still open
+#[ic_cdk::query]
+fn get_turnout(kind: ProposeKind, id: usize) -> Result<Turnout, ContractError> {
+    observer_guard()?;
+
+    let (voters, electorate) = match kind {
+        ProposeKind::Committee => {
+            let (voters, district_id) = COMMITTEE_PROPOSALS.with(|committee_proposals| {
+                let committee_proposals = committee_proposals.borrow();
+                Ok::<_, ContractError>((
+                    committee_proposals.get_voters(id)?,
+                    committee_proposals.get_district_id(id)?,
+                ))
+            })?;
+            // Only the committee the propose was raised in votes on it
+            (
+                voters,
+                USERS.with(|users| users.borrow().get_level_committee_size(district_id))? as usize,
+            )
+        }
+        ProposeKind::PresidentialElections => {
+            let (voters, district_id) = PRESIDENTIAL_ELECTIONS.with(|proposals| {
+                let proposals = proposals.borrow();
+                Ok::<_, ContractError>((proposals.get_voters(id)?, proposals.get_district_id(id)?))
+            })?;
+            (
+                voters,
+                USERS.with(|users| users.borrow().get_electorate(district_id).0),
+            )
+        }
+        ProposeKind::Ballot => (
+            BALLOTS.with(|ballots| ballots.borrow().get_voters(id))?,
+            USERS.with(|users| users.borrow().get_electorate(None).0),
+        ),
+    };
+
+    Ok(Turnout {
+        voters_count: voters.len(),
+        electorate,
+    })
+}
+
 #[ic_cdk::query]
 fn get_committee_size() -> usize {
     USERS.with(|users| users.borrow().get_committee_size())
@@ -151,24 +728,80 @@ fn get_users_count() -> usize {
 }
 
 #[ic_cdk::query]
-fn user_belongs_to_committee() -> bool {
-    let caller = caller().unwrap();
+fn user_belongs_to_committee() -> Result<bool, ContractError> {
+    let caller = caller()?;
 
-    USERS
-        .with(|users| users.borrow().is_in_committee(caller))
-        .unwrap()
+    USERS.with(|users| users.borrow().is_in_committee(caller))
 }
 
 // System actions
 
-fn committee_guard() -> Result<(), String> {
-    let caller = caller().unwrap();
+fn record_event(kind: EventKind) {
+    let head_hash = EVENTS.with(|events| events.borrow_mut().record(kind));
+    ic_cdk::api::set_certified_data(&head_hash);
+}
+
+// Returns the caller when they belong to the committee
+fn committee_guard() -> Result<Principal, ContractError> {
+    let caller = caller()?;
 
     if !USERS.with(|users| users.borrow().is_in_committee(caller))? {
-        return Err(ContractError::NotInCommittee.to_string());
+        return Err(ContractError::NotInCommittee { principal: caller });
     }
 
-    Ok(())
+    Ok(caller)
+}
+
+// Returns the caller when they belong to the committee of the district, or to the one outside districts when none is given
+fn district_committee_guard(district_id: Option<u64>) -> Result<Principal, ContractError> {
+    let caller = committee_guard()?;
+
+    if USERS.with(|users| users.borrow().get_district_id(caller))? != district_id {
+        return Err(ContractError::NotInDistrictCommittee {
+            district_id,
+            principal: caller,
+        });
+    }
+
+    Ok(caller)
+}
+
+// Committee deciding the action, the one of the district it targets
+fn committee_scope(action: &CommitteeActions) -> Result<Option<u64>, ContractError> {
+    let district_id = match action {
+        CommitteeActions::ResolvePresidentialElectionsTie { propose_id, .. } => {
+            PRESIDENTIAL_ELECTIONS.with(|presidential_elections| {
+                presidential_elections.borrow().get_district_id(*propose_id)
+            })?
+        }
+        action => action.district_id(),
+    };
+
+    USERS.with(|users| users.borrow().get_governing_district(district_id))
+}
+
+// District of the caller when they may vote, candidates and signers of nominations have to be voters
+fn eligible_voter_district(caller: Principal) -> Result<Option<u64>, ContractError> {
+    USERS.with(|users| {
+        let users = users.borrow();
+        users.get_voting_weight(caller)?;
+        users.get_district_id(caller)
+    })
+}
+
+// Returns the caller when they may read privileged data, as a committee member or an observer
+fn observer_guard() -> Result<Principal, ContractError> {
+    let caller = caller()?;
+
+    let allowed = USERS.with(|users| {
+        let users = users.borrow();
+        Ok::<_, ContractError>(users.is_in_committee(caller)? || users.is_observer(caller)?)
+    })?;
+    if !allowed {
+        return Err(ContractError::NotObserver { principal: caller });
+    }
+
+    Ok(caller)
 }
 
 fn close_committee_proposal(id: usize) {
@@ -177,7 +810,12 @@ fn close_committee_proposal(id: usize) {
         .ok_or(ContractError::ConfigNotSet)
         .unwrap();
 
-    let committee_size = USERS.with(|users| users.borrow().get_committee_size());
+    let district_id = COMMITTEE_PROPOSALS
+        .with(|committee_proposals| committee_proposals.borrow().get_district_id(id))
+        .unwrap();
+    let committee_size = USERS
+        .with(|users| users.borrow().get_level_committee_size(district_id))
+        .unwrap() as usize;
 
     COMMITTEE_PROPOSALS
         .with(|committee_proposals| {
@@ -193,61 +831,384 @@ fn close_presidential_elections(id: usize) {
         .with(|config| config.borrow().clone())
         .ok_or(ContractError::ConfigNotSet)
         .unwrap();
-    let users_count = USERS.with(|users| users.borrow().len());
+    let district_id = PRESIDENTIAL_ELECTIONS
+        .with(|presidential_elections| presidential_elections.borrow().get_district_id(id))
+        .unwrap();
+    let (users_count, total_voting_weight, district_parents) = USERS.with(|users| {
+        let users = users.borrow();
+        let (users_count, total_voting_weight) = users.get_electorate(district_id);
+        (
+            users_count,
+            total_voting_weight,
+            users.get_district_parents(),
+        )
+    });
+    let voters = PRESIDENTIAL_ELECTIONS
+        .with(|presidential_elections| presidential_elections.borrow().get_voters(id))
+        .unwrap();
+    let delegated = delegated_voting_weights(DelegationTopic::PresidentialElections, &voters);
+
+    let awaits_lot = PRESIDENTIAL_ELECTIONS
+        .with(|presidential_elections| {
+            let mut presidential_elections = presidential_elections.borrow_mut();
+            presidential_elections
+                .close_proposal(
+                    config,
+                    id,
+                    users_count,
+                    total_voting_weight,
+                    &delegated,
+                    &district_parents,
+                )
+                .map(|_| presidential_elections.awaits_lot(id))
+        })
+        .unwrap();
+
+    if awaits_lot {
+        ic_cdk::spawn(draw_presidential_elections_tie(id));
+    }
+}
+
+// Lot is drawn again after a failed request for randomness or an upgrade, in a message of its own
+fn schedule_presidential_elections_lot(id: usize, delay: std::time::Duration) {
+    ic_cdk_timers::set_timer(delay, move || {
+        ic_cdk::spawn(draw_presidential_elections_tie(id))
+    });
+}
+
+async fn draw_presidential_elections_tie(id: usize) {
+    let seed = match raw_rand().await {
+        Ok((seed,)) => seed,
+        Err((code, message)) => {
+            println!(
+                "Lot of presidential vote with id: {:?} could not get randomness ({:?}: {}), drawing again",
+                id, code, message
+            );
+            schedule_presidential_elections_lot(id, LOT_RETRY_DELAY);
+            return;
+        }
+    };
+
+    let config = CONFIG
+        .with(|config| config.borrow().clone())
+        .ok_or(ContractError::ConfigNotSet)
+        .unwrap();
 
     PRESIDENTIAL_ELECTIONS
         .with(|presidential_elections| {
             presidential_elections
                 .borrow_mut()
-                .close_proposal(config, id, users_count)
+                .resolve_tie_by_lot(config, id, seed)
         })
         .unwrap();
 }
 
-fn register_new_entry_identities(entry_identities: &Vec<Principal>) {
+fn resolve_presidential_elections_tie(
+    propose_id: usize,
+    candidates: &[usize],
+) -> Result<(), ContractError> {
+    let config = CONFIG
+        .with(|config| config.borrow().clone())
+        .ok_or(ContractError::ConfigNotSet)?;
+
+    PRESIDENTIAL_ELECTIONS.with(|presidential_elections| {
+        presidential_elections
+            .borrow_mut()
+            .resolve_tie_by_committee(config, propose_id, candidates)
+    })
+}
+
+fn close_ballot(id: usize) {
+    let config = CONFIG
+        .with(|config| config.borrow().clone())
+        .ok_or(ContractError::ConfigNotSet)
+        .unwrap();
+
+    let voters = BALLOTS
+        .with(|ballots| ballots.borrow().get_voters(id))
+        .unwrap();
+    let delegated = delegated_voting_weights(DelegationTopic::Ballot, &voters);
+
+    BALLOTS
+        .with(|ballots| ballots.borrow_mut().close_proposal(config, id, &delegated))
+        .unwrap();
+}
+
+// Current identities of the voters, ballots are kept under entry identities while delegations link identities
+fn voter_identities(entry_identities: &[Principal]) -> Vec<Principal> {
     USERS.with(|users| {
+        let users = users.borrow();
         entry_identities
             .iter()
-            .for_each(|entry_identity| users.borrow_mut().push(User::new(entry_identity)))
-    });
+            .filter_map(|entry_identity| users.get_identity(*entry_identity))
+            .collect()
+    })
 }
 
-fn promote_user(user_entry_identity: &Principal) -> Result<(), String> {
+// Voting weight and district of every delegator who did not vote, paired with the entry identity of the direct voter representing them
+fn delegated_voting_weights(
+    topic: DelegationTopic,
+    voters: &[Principal],
+) -> Vec<(Principal, u64, Option<u64>)> {
+    let edges = DELEGATIONS.with(|delegations| {
+        delegations
+            .borrow()
+            .resolve(topic, &voter_identities(voters))
+    });
+
     USERS.with(|users| {
-        let mut users = users.borrow_mut();
-        let user = users
-            .get_mut_user_by_identity(*user_entry_identity)
-            .ok_or(ContractError::ProposeNotFound.to_string())?;
-        user.promote();
-        Ok(())
+        let users = users.borrow();
+        edges
+            .into_iter()
+            .filter(|edge| !edge.voted_directly)
+            .filter_map(|edge| {
+                let voting_weight = users.get_voting_weight(edge.delegator).ok()?;
+                let district_id = users.get_district_id(edge.delegator).ok()?;
+                let representative = users.get_voter(edge.representative?).ok()?;
+                Some((representative.entry_identity, voting_weight, district_id))
+            })
+            .collect()
     })
 }
 
-fn demote_user(user_entry_identity: &Principal) -> Result<(), String> {
-    USERS.with(|users| {
+fn register_new_entry_identities(
+    entry_identities: &[Principal],
+    activation_deadline: Option<u64>,
+    district_id: Option<u64>,
+) -> Result<(), ContractError> {
+    let batch_id = USERS.with(|users| {
         let mut users = users.borrow_mut();
-        let user = users
-            .get_mut_user_by_identity(*user_entry_identity)
-            .ok_or(ContractError::ProposeNotFound.to_string())?;
-        user.demote();
-        Ok(())
-    })
+        let mut registered = BTreeSet::new();
+
+        if let Some(entry_identity) = entry_identities.iter().find(|entry_identity| {
+            users.contains_entry_identity(**entry_identity) || !registered.insert(**entry_identity)
+        }) {
+            return Err(ContractError::EntryIdentityAlreadyRegistered {
+                entry_identity: *entry_identity,
+            });
+        }
+
+        let batch_id =
+            users.push_batch(entry_identities, activation_deadline, None, district_id)?;
+        entry_identities.iter().for_each(|entry_identity| {
+            record_event(EventKind::UserRegistered {
+                entry_identity: *entry_identity,
+                role: Role::User,
+            });
+        });
+        Ok(batch_id)
+    })?;
+
+    if let Some(activation_deadline) = activation_deadline {
+        ic_cdk_timers::set_timer(time_until(activation_deadline), move || {
+            expire_registration_batch(batch_id)
+        });
+    }
+    Ok(())
+}
+
+fn approve_voter_roll(
+    merkle_root: &[u8],
+    activation_deadline: Option<u64>,
+    district_id: Option<u64>,
+) -> Result<(), ContractError> {
+    let batch_id = USERS.with(|users| {
+        users.borrow_mut().push_batch(
+            &[],
+            activation_deadline,
+            Some(merkle_root.to_vec()),
+            district_id,
+        )
+    })?;
+    record_event(EventKind::VoterRollApproved {
+        batch_id,
+        merkle_root: merkle_root.to_vec(),
+    });
+    Ok(())
 }
 
-fn create_user_propose(propose: &UserPropose, creator: Principal) -> Result<(), String> {
+fn announce_presidential_elections(
+    creator: Principal,
+    weighted: bool,
+    district_id: Option<u64>,
+) -> Result<(), ContractError> {
+    let config = CONFIG
+        .with(|config| config.borrow().clone())
+        .ok_or(ContractError::ConfigNotSet)?;
+    if let Some(district_id) = district_id
+        .filter(|district_id| !USERS.with(|users| users.borrow().contains_district(*district_id)))
+    {
+        return Err(ContractError::DistrictNotFound { district_id });
+    }
+
+    let id = ANNOUNCEMENTS.with(|announcements| {
+        announcements
+            .borrow_mut()
+            .announce(&config, creator, weighted, district_id)
+    });
+    record_event(EventKind::ElectionsAnnounced { id, creator });
+    Ok(())
+}
+
+// Elections start only when at least one candidate collected the required signatures
+fn close_candidate_registration(id: usize) {
     let config = CONFIG
         .with(|config| config.borrow().clone())
         .ok_or(ContractError::ConfigNotSet)
         .unwrap();
+    let (creator, weighted, district_id, nominated) = ANNOUNCEMENTS
+        .with(|announcements| announcements.borrow_mut().close_registration(&config, id))
+        .unwrap();
+    let (candidates, nominated): (Vec<String>, Vec<Principal>) = nominated.into_iter().unzip();
+
+    let election_id = (!candidates.is_empty()).then(|| {
+        let election_id = PRESIDENTIAL_ELECTIONS.with(|presidential_elections| {
+            let mut presidential_elections = presidential_elections.borrow_mut();
+            let election_id = presidential_elections.next_id();
+            presidential_elections.create_proposal(
+                config,
+                creator,
+                &candidates,
+                nominated.iter().copied().map(Some).collect(),
+                weighted,
+                district_id,
+            );
+            election_id
+        });
+        ANNOUNCEMENTS
+            .with(|announcements| announcements.borrow_mut().set_election_id(id, election_id));
+        election_id
+    });
+
+    record_event(EventKind::CandidateRegistrationClosed {
+        id,
+        nominated,
+        election_id,
+    });
+}
+
+fn create_district(name: &str, parent_id: Option<u64>) -> Result<(), ContractError> {
+    let id = USERS.with(|users| {
+        users
+            .borrow_mut()
+            .create_district(name.to_string(), parent_id)
+    })?;
+    record_event(EventKind::DistrictCreated {
+        id,
+        name: name.to_string(),
+        parent_id,
+    });
+    Ok(())
+}
+
+fn expire_registration_batch(batch_id: u64) {
+    if let Some(expired) = USERS.with(|users| users.borrow_mut().expire_batch(batch_id)) {
+        record_event(EventKind::EntryIdentitiesExpired { batch_id, expired });
+    }
+}
+
+fn promote_user(user_entry_identity: &Principal) -> Result<(), ContractError> {
+    let config = CONFIG
+        .with(|config| config.borrow().clone())
+        .ok_or(ContractError::ConfigNotSet)?;
+
+    USERS.with(|users| users.borrow_mut().promote(&config, *user_entry_identity))?;
+    record_event(EventKind::UserPromoted(*user_entry_identity));
+    Ok(())
+}
+
+fn reset_user_identity(user_entry_identity: &Principal) -> Result<(), ContractError> {
+    let identity = USERS.with(|users| users.borrow_mut().reset_identity(*user_entry_identity))?;
+    DELEGATIONS.with(|delegations| delegations.borrow_mut().drop_identity(identity));
+    record_event(EventKind::UserIdentityReset {
+        entry_identity: *user_entry_identity,
+        identity,
+    });
+    Ok(())
+}
+
+fn set_user_status(
+    user_entry_identity: &Principal,
+    status: UserStatus,
+) -> Result<(), ContractError> {
+    USERS.with(|users| {
+        users
+            .borrow_mut()
+            .set_status(*user_entry_identity, status.clone())
+    })?;
+    record_event(EventKind::UserStatusChanged {
+        entry_identity: *user_entry_identity,
+        status,
+    });
+    Ok(())
+}
+
+fn demote_user(user_entry_identity: &Principal) -> Result<(), ContractError> {
+    USERS.with(|users| users.borrow_mut().demote(*user_entry_identity))?;
+    record_event(EventKind::UserDemoted(*user_entry_identity));
+    Ok(())
+}
+
+fn appoint_observer(user_identity: &Principal) -> Result<(), ContractError> {
+    USERS.with(|users| users.borrow_mut().appoint_observer(*user_identity))?;
+    record_event(EventKind::ObserverAppointed(*user_identity));
+    Ok(())
+}
+
+fn dismiss_observer(user_identity: &Principal) -> Result<(), ContractError> {
+    USERS.with(|users| users.borrow_mut().dismiss_observer(*user_identity))?;
+    record_event(EventKind::ObserverDismissed(*user_identity));
+    Ok(())
+}
+
+fn set_voting_weight(user_identity: &Principal, voting_weight: u64) -> Result<(), ContractError> {
+    USERS.with(|users| {
+        users
+            .borrow_mut()
+            .set_voting_weight(*user_identity, voting_weight)
+    })?;
+    record_event(EventKind::VotingWeightChanged {
+        identity: *user_identity,
+        voting_weight,
+    });
+    Ok(())
+}
+
+fn create_user_propose(propose: &UserPropose, creator: Principal) -> Result<(), ContractError> {
+    let config = CONFIG
+        .with(|config| config.borrow().clone())
+        .ok_or(ContractError::ConfigNotSet)?;
 
     match propose {
-        UserPropose::PresidentialElections(proposal_content) => {
+        UserPropose::PresidentialElections {
+            candidates,
+            weighted,
+            district_id,
+        } => {
+            if let Some(district_id) = district_id.filter(|district_id| {
+                !USERS.with(|users| users.borrow().contains_district(*district_id))
+            }) {
+                return Err(ContractError::DistrictNotFound { district_id });
+            }
             PRESIDENTIAL_ELECTIONS.with(|propose| {
-                propose
-                    .borrow_mut()
-                    .create_proposal(config, creator, &proposal_content)
+                propose.borrow_mut().create_proposal(
+                    config,
+                    creator,
+                    candidates,
+                    vec![None; candidates.len()],
+                    *weighted,
+                    *district_id,
+                )
             })
         }
+        UserPropose::Ballot {
+            questions,
+            weighted,
+        } => BALLOTS.with(|ballots| {
+            ballots
+                .borrow_mut()
+                .create_proposal(config, creator, questions, *weighted)
+        }),
     };
     Ok(())
 }
@@ -1,12 +1,23 @@
-use std::collections::{BTreeMap, HashMap};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+};
 
-use candid::{CandidType, Deserialize, Principal};
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
 use ic_cdk::println;
-use ic_cdk_timers::TimerId;
+use ic_stable_structures::{
+    memory_manager::VirtualMemory, storable::Bound, DefaultMemoryImpl, StableBTreeMap, StableCell,
+    StableLog, Storable,
+};
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
 
 use crate::{
-    close_committee_proposal, close_presidential_elections, create_user_propose, demote_user,
-    errors::ContractError, promote_user, register_new_entry_identities,
+    announce_presidential_elections, appoint_observer, approve_voter_roll, close_ballot,
+    close_candidate_registration, close_committee_proposal, close_presidential_elections,
+    create_district, create_user_propose, demote_user, dismiss_observer, errors::ContractError,
+    helpers::rearm_timers, promote_user, record_event, register_new_entry_identities,
+    reset_user_identity, resolve_presidential_elections_tie, set_user_status, set_voting_weight,
 };
 
 #[derive(Clone, Debug, CandidType, Deserialize)]
@@ -16,15 +27,169 @@ pub struct Config {
     pub committee_proposals_duration: u64,
     pub user_proposals_duration: u64,
     pub presidential_elections_threshold: u16,
+    pub tie_break_policy: TieBreakPolicy,
+    pub runoff_rules: RunoffRules,
+    // Voters may replace their ballot and committee members may retract their vote while the vote is open
+    pub allow_revoting: bool,
+    // Time in nanoseconds a user has to wait after an identity reset before activating a new identity
+    pub identity_reset_cooldown: u64,
+    // When set, the registry holds `hash_entry_identity` of every entry identity instead of the identity itself
+    pub entry_identity_salt: Option<Vec<u8>>,
+    // Committee size of a district by its population, districts above every bracket get `max_committee_size`
+    pub committee_size_brackets: Vec<CommitteeSizeBracket>,
+    // Time in nanoseconds users have to declare candidacy and sign nominations of announced elections
+    pub candidate_registration_duration: u64,
+    // Signatures a candidate needs to appear on the ballot
+    pub nomination_signatures_required: u64,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct CommitteeSizeBracket {
+    pub max_population: u64,
+    pub committee_size: u64,
+}
+
+impl Config {
+    pub fn committee_size_for(&self, population: u64) -> u64 {
+        self.committee_size_brackets
+            .iter()
+            .filter(|bracket| population <= bracket.max_population)
+            .min_by_key(|bracket| bracket.max_population)
+            .map_or(self.max_committee_size, |bracket| bracket.committee_size)
+    }
+
+    // Entry identity under which the caller is kept in the registry
+    pub fn registered_entry_identity(&self, entry_identity: Principal) -> Principal {
+        match &self.entry_identity_salt {
+            Some(salt) => hash_entry_identity(salt, &entry_identity),
+            None => entry_identity,
+        }
+    }
+
+    // Config safe to publish in the audit log, the salt is replaced by its SHA-256 so it can be checked once revealed
+    pub fn redacted(&self) -> Self {
+        Self {
+            entry_identity_salt: self
+                .entry_identity_salt
+                .as_ref()
+                .map(|salt| Sha256::digest(salt).to_vec()),
+            ..self.clone()
+        }
+    }
+}
+
+const OPAQUE_PRINCIPAL_CLASS: u8 = 0x01;
+
+const MERKLE_LEAF_PREFIX: u8 = 0x00;
+const MERKLE_NODE_PREFIX: u8 = 0x01;
+
+pub fn merkle_leaf(entry_identity: &Principal) -> Vec<u8> {
+    Sha256::new()
+        .chain_update([MERKLE_LEAF_PREFIX])
+        .chain_update(entry_identity.as_slice())
+        .finalize()
+        .to_vec()
+}
+
+// Pairs are hashed in sorted order, so a proof is just the list of siblings from the leaf up
+pub fn verify_inclusion(root: &[u8], entry_identity: &Principal, proof: &[Vec<u8>]) -> bool {
+    let computed = proof
+        .iter()
+        .fold(merkle_leaf(entry_identity), |node, sibling| {
+            let (left, right) = if node <= *sibling {
+                (&node, sibling)
+            } else {
+                (sibling, &node)
+            };
+            Sha256::new()
+                .chain_update([MERKLE_NODE_PREFIX])
+                .chain_update(left)
+                .chain_update(right)
+                .finalize()
+                .to_vec()
+        });
+
+    computed == root
+}
+
+// Opaque principal built from the salted hash, so it fits everywhere an entry identity does but can not be enumerated
+pub fn hash_entry_identity(salt: &[u8], entry_identity: &Principal) -> Principal {
+    let hash = Sha256::new()
+        .chain_update(salt)
+        .chain_update(entry_identity.as_slice())
+        .finalize();
+
+    Principal::from_slice(&[&hash[..28], &[OPAQUE_PRINCIPAL_CLASS]].concat())
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct RunoffRules {
+    // Rounds counted together with the first one, no runoff is created past this limit
+    pub max_rounds: u32,
+    // Every candidate tied at the runoff cutoff goes to the runoff instead of going through the tie break
+    pub expand_tied_runoff: bool,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub enum TieBreakPolicy {
+    // Tied candidates go to another round on their own
+    Runoff,
+    // Committee picks the tied candidates with `ResolvePresidentialElectionsTie`
+    CommitteeDecision,
+    // Tied candidates are drawn using randomness of the management canister
+    Lot,
 }
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub enum TieBreakStage {
+    Winner,
+    RunoffCutoff,
+}
+
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct TieBreak {
+    pub policy: TieBreakPolicy,
+    pub stage: TieBreakStage,
+    pub tied_candidates: Vec<usize>,
+    // Randomness used for the lot, kept so the draw can be audited
+    pub seed: Option<Vec<u8>>,
+    pub chosen: Vec<usize>,
+}
+
+impl TieBreak {
+    pub fn new(policy: &TieBreakPolicy, stage: TieBreakStage, tied_candidates: &[usize]) -> Self {
+        Self {
+            policy: policy.clone(),
+            stage,
+            tied_candidates: tied_candidates.to_vec(),
+            seed: None,
+            chosen: Vec::default(),
+        }
+    }
+
+    fn is_pending(&self, policy: TieBreakPolicy) -> bool {
+        self.policy == policy && self.chosen.is_empty()
+    }
+}
+
+#[derive(CandidType, Deserialize, PartialEq, Clone, Copy)]
 pub enum Role {
     Committee,
     User,
+    // Reads turnout, the audit log and committee votes, but neither votes nor proposes
+    Observer,
 }
 
-#[derive(Clone)]
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub enum UserStatus {
+    Active,
+    // Temporarily excluded, the committee may reinstate the user
+    Suspended { reason: String },
+    // Permanently removed from the roll, e.g. deceased or relocated
+    Revoked { reason: String },
+}
+
+#[derive(CandidType, Deserialize, Clone)]
 pub struct User {
     // Entry data provided by Committee used to register a user
     entry_identity: Principal,
@@ -33,6 +198,16 @@ pub struct User {
     // Seed used to generate the identity
     identity_seed: Option<String>,
     role: Role,
+    status: UserStatus,
+    // Weight of the user vote in weighted elections, e.g. apartment share size
+    voting_weight: u64,
+    // Time of the last identity reset approved by the committee
+    identity_reset_at: Option<u64>,
+    // Identity the currently active one agreed to hand the account over to
+    pending_identity: Option<Principal>,
+    // Registration batch of users registered by the committee, founding members have none
+    batch_id: Option<u64>,
+    district_id: Option<u64>,
 }
 
 impl User {
@@ -42,6 +217,12 @@ impl User {
             identity: None,
             identity_seed: None,
             role: Role::User,
+            status: UserStatus::Active,
+            voting_weight: 1,
+            identity_reset_at: None,
+            pending_identity: None,
+            batch_id: None,
+            district_id: None,
         }
     }
 
@@ -53,12 +234,30 @@ impl User {
         self.role = Role::User
     }
 
+    pub fn appoint_observer(&mut self) {
+        self.role = Role::Observer
+    }
+
+    pub fn set_voting_weight(&mut self, voting_weight: u64) {
+        self.voting_weight = voting_weight
+    }
+
+    pub fn get_voting_weight(&self) -> u64 {
+        self.voting_weight
+    }
+
     pub fn new_with_role(entry_identity: &Principal, role: Role) -> Self {
         Self {
             entry_identity: *entry_identity,
             identity: None,
             identity_seed: None,
             role,
+            status: UserStatus::Active,
+            voting_weight: 1,
+            identity_reset_at: None,
+            pending_identity: None,
+            batch_id: None,
+            district_id: None,
         }
     }
 
@@ -77,11 +276,83 @@ impl User {
     pub fn activate(&mut self, identity: Principal, identity_seed: String) {
         self.identity = Some(identity);
         self.identity_seed = Some(identity_seed);
+        self.pending_identity = None;
+    }
+
+    pub fn reset_identity(&mut self, reset_at: u64) {
+        self.identity = None;
+        self.identity_seed = None;
+        self.identity_reset_at = Some(reset_at);
+        self.pending_identity = None;
     }
 
     pub fn is_in_committee(&self) -> bool {
         self.role == Role::Committee
     }
+
+    pub fn is_observer(&self) -> bool {
+        self.role == Role::Observer
+    }
+
+    pub fn new_in_batch(
+        entry_identity: &Principal,
+        batch_id: u64,
+        district_id: Option<u64>,
+    ) -> Self {
+        Self {
+            batch_id: Some(batch_id),
+            district_id,
+            ..Self::new(entry_identity)
+        }
+    }
+
+    pub fn get_district_id(&self) -> Option<u64> {
+        self.district_id
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.status == UserStatus::Active
+    }
+
+    pub fn get_status(&self) -> &UserStatus {
+        &self.status
+    }
+
+    pub fn set_status(&mut self, status: UserStatus) {
+        self.status = status
+    }
+}
+
+impl Storable for User {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Choice of a direct voter together with the weight it was counted with
+#[derive(CandidType, Deserialize, Clone)]
+pub struct CastVote<T> {
+    pub choice: T,
+    pub weight: u64,
+    pub district_id: Option<u64>,
+}
+
+impl<T: CandidType + DeserializeOwned> Storable for CastVote<T> {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
 }
 
 #[derive(CandidType, Deserialize, Clone, PartialEq, Debug)]
@@ -91,17 +362,356 @@ pub enum VoteState {
     Rejected,
     Unresolved,
     Cancelled,
+    // Accepted by the committee but the action could not be executed
+    Failed { reason: Box<ContractError> },
+}
+
+#[derive(CandidType, Deserialize, Clone, Default)]
+pub struct ProposeFilter {
+    // Matched on the variant only, so `Failed` matches every failure reason
+    pub state: Option<VoteState>,
+    pub creator: Option<Principal>,
+    pub created_after: Option<u64>,
+    pub created_before: Option<u64>,
+}
+
+impl ProposeFilter {
+    pub fn matches(&self, state: &VoteState, creator: &Principal, created_at: u64) -> bool {
+        self.state.as_ref().is_none_or(|filter_state| {
+            std::mem::discriminant(filter_state) == std::mem::discriminant(state)
+        }) && self
+            .creator
+            .is_none_or(|filter_creator| &filter_creator == creator)
+            && self.created_after.is_none_or(|after| created_at >= after)
+            && self.created_before.is_none_or(|before| created_at < before)
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    // Id to pass as the cursor of the next call, none once every propose was scanned
+    pub next_cursor: Option<usize>,
+}
+
+pub const MAX_PAGE_SIZE: usize = 100;
+
+fn paginate<P, T>(
+    proposals: &[P],
+    cursor: Option<usize>,
+    limit: usize,
+    matches: impl Fn(&P) -> bool,
+    map: impl Fn(&P) -> T,
+) -> Page<T> {
+    // An empty page would hand back the same cursor and a client following it would never finish
+    let limit = limit.clamp(1, MAX_PAGE_SIZE);
+    let mut items = Vec::default();
+    let mut next_cursor = None;
+
+    for (id, propose) in proposals.iter().enumerate().skip(cursor.unwrap_or(0)) {
+        if items.len() == limit {
+            next_cursor = Some(id);
+            break;
+        }
+        if matches(propose) {
+            items.push(map(propose));
+        }
+    }
+
+    Page { items, next_cursor }
 }
 
+#[derive(CandidType, Deserialize)]
 pub struct PresidentialElectionsPropose {
     pub id: usize,
-    _timer_id: TimerId,
     pub creator: Principal,
     pub proposal_content: Vec<String>,
+    // Principals of nominated candidates in the order of `proposal_content`, candidates named by the committee have none
+    pub candidates: Vec<Option<Principal>>,
     pub created_at: u64,
     pub state: VoteState,
     pub votes_yes: Vec<u64>,
-    pub voters: Vec<Principal>,
+    pub winner: Option<usize>,
+    pub tie_break: Option<TieBreak>,
+    pub parent_id: Option<usize>,
+    pub round: u32,
+    pub weighted: bool,
+    pub voters_count: usize,
+    pub delegated_votes: Vec<u64>,
+    // Only users of the district vote and its population is the electorate
+    pub district_id: Option<u64>,
+    // Votes broken down by the district of the voter, users without a district count only in `votes_yes`
+    pub district_votes: BTreeMap<u64, Vec<u64>>,
+    // Totals of every level computed on close, each level's committee certifies its own node
+    pub results: Option<ResultsNode>,
+}
+
+impl PresidentialElectionsPropose {
+    fn tally(&mut self, choice: usize, weight: u64, district_id: Option<u64>) {
+        self.votes_yes[choice] += weight;
+        if let Some(district_id) = district_id {
+            let candidates_count = self.votes_yes.len();
+            self.district_votes
+                .entry(district_id)
+                .or_insert_with(|| vec![0; candidates_count])[choice] += weight;
+        }
+    }
+
+    fn untally(&mut self, cast_vote: &CastVote<usize>) {
+        self.votes_yes[cast_vote.choice] -= cast_vote.weight;
+        if let Some(votes) = cast_vote
+            .district_id
+            .and_then(|district_id| self.district_votes.get_mut(&district_id))
+        {
+            votes[cast_vote.choice] -= cast_vote.weight;
+        }
+    }
+
+    fn candidates_with_votes(&self, votes: u64) -> Vec<usize> {
+        self.votes_yes
+            .iter()
+            .enumerate()
+            .filter(|(_, candidate_votes)| **candidate_votes == votes)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    // Candidate indexes ordered by votes, ties keep the ballot order
+    fn ranking(&self) -> Vec<usize> {
+        let mut ranking: Vec<usize> = (0..self.votes_yes.len()).collect();
+        ranking
+            .sort_by(|index, next_index| self.votes_yes[*next_index].cmp(&self.votes_yes[*index]));
+        ranking
+    }
+
+    fn candidates_names(&self, indexes: &[usize]) -> Vec<String> {
+        indexes
+            .iter()
+            .map(|index| {
+                self.proposal_content
+                    .get(*index)
+                    .expect("Propose do not exist!?")
+                    .clone()
+            })
+            .collect()
+    }
+
+    fn count_delegated_votes(
+        &mut self,
+        ballots: &VoterBallots<CastVote<usize>>,
+        delegated: &[(Principal, u64, Option<u64>)],
+        district_parents: &BTreeMap<u64, Option<u64>>,
+    ) {
+        for (representative, voting_weight, district_id) in delegated {
+            if !is_within_district(district_parents, *district_id, self.district_id) {
+                continue;
+            }
+            if let Some(cast_vote) = ballots.get(self.id, *representative) {
+                let weight = if self.weighted { *voting_weight } else { 1 };
+                self.tally(cast_vote.choice, weight, *district_id);
+                self.delegated_votes[cast_vote.choice] += weight;
+            }
+        }
+    }
+
+    // Nationwide elections span every district, a district-scoped one only its own subtree
+    fn aggregate_results(&self, district_parents: &BTreeMap<u64, Option<u64>>) -> ResultsNode {
+        let mut root = ResultsNode::aggregate(
+            self.district_id,
+            &self.district_votes,
+            district_parents,
+            self.votes_yes.len(),
+        );
+        if self.district_id.is_none() {
+            // Users without a district are counted only nationwide
+            root.votes_yes = self.votes_yes.clone();
+        }
+        root
+    }
+
+    fn runoff_allowed(&self, config: &Config) -> bool {
+        self.round < config.runoff_rules.max_rounds
+    }
+
+    // Returns candidates of the next round when the tie has to be settled by a runoff
+    fn settle_winner(&mut self, config: &Config, leaders: Vec<usize>) -> Option<Vec<usize>> {
+        if leaders.len() == 1 {
+            self.winner = leaders.first().copied();
+            self.state = VoteState::Accepted;
+            return None;
+        }
+
+        let policy = &config.tie_break_policy;
+        let mut tie_break = TieBreak::new(policy, TieBreakStage::Winner, &leaders);
+        self.state = VoteState::Unresolved;
+
+        if policy == &TieBreakPolicy::Runoff && self.runoff_allowed(config) {
+            tie_break.chosen = leaders.clone();
+            self.tie_break = Some(tie_break);
+            return Some(leaders);
+        }
+
+        self.tie_break = Some(tie_break);
+        None
+    }
+
+    // Returns candidates of the next round, `None` when runoff seats await the tie break
+    fn select_runoff(&mut self, config: &Config) -> Option<Vec<usize>> {
+        self.state = VoteState::Unresolved;
+
+        if !self.runoff_allowed(config) {
+            return None;
+        }
+
+        let ranking = self.ranking();
+        let cutoff = self.votes_yes[ranking[1]];
+        let runoff: Vec<usize> = ranking
+            .into_iter()
+            .filter(|index| self.votes_yes[*index] >= cutoff)
+            .collect();
+
+        if runoff.len() == 2 {
+            return Some(runoff);
+        }
+
+        let policy = &config.tie_break_policy;
+        let tied = self.candidates_with_votes(cutoff);
+        let mut tie_break = TieBreak::new(policy, TieBreakStage::RunoffCutoff, &tied);
+
+        if config.runoff_rules.expand_tied_runoff || policy == &TieBreakPolicy::Runoff {
+            tie_break.chosen = tied;
+            self.tie_break = Some(tie_break);
+            return Some(runoff);
+        }
+
+        self.tie_break = Some(tie_break);
+        None
+    }
+
+    // Candidates with a runoff seat regardless of the tie and the number of seats left for tied ones
+    fn tie_break_seats(&self, tie_break: &TieBreak) -> (Vec<usize>, usize) {
+        let tied_votes = self.votes_yes[tie_break.tied_candidates[0]];
+        let qualified: Vec<usize> = self
+            .ranking()
+            .into_iter()
+            .filter(|index| self.votes_yes[*index] > tied_votes)
+            .collect();
+        let seats = match tie_break.stage {
+            TieBreakStage::Winner => 1,
+            TieBreakStage::RunoffCutoff => 2 - qualified.len(),
+        };
+
+        (qualified, seats)
+    }
+
+    // Returns candidates of the next round when the chosen ones fill the runoff seats
+    fn apply_tie_break(&mut self, qualified: Vec<usize>, chosen: Vec<usize>) -> Option<Vec<usize>> {
+        let tie_break = self.tie_break.as_mut()?;
+        tie_break.chosen = chosen.clone();
+
+        match tie_break.stage {
+            TieBreakStage::Winner => {
+                self.winner = chosen.first().copied();
+                self.state = VoteState::Accepted;
+                println!(
+                    "Presidential vote with id: {:?} has been {:?} by tie break. Winner: {:?}",
+                    self.id, self.state, self.winner
+                );
+                None
+            }
+            TieBreakStage::RunoffCutoff => Some(qualified.into_iter().chain(chosen).collect()),
+        }
+    }
+}
+
+// Picks `slots` candidates, each draw consumes 8 bytes of the seed
+fn draw_lots(seed: &[u8], candidates: &[usize], slots: usize) -> Vec<usize> {
+    let mut pool = candidates.to_vec();
+
+    seed.chunks_exact(8)
+        .take(slots)
+        .map(|chunk| {
+            let roll = u64::from_le_bytes(chunk.try_into().expect("Chunk has 8 bytes"));
+            pool.remove((roll % pool.len() as u64) as usize)
+        })
+        .collect()
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct ResultsNode {
+    // `None` stands for the national level
+    pub district_id: Option<u64>,
+    // Votes of the district and of every district below it
+    pub votes_yes: Vec<u64>,
+    pub certified_by: Vec<Principal>,
+    // Certifications of the level's committee reached the committee threshold
+    pub certified: bool,
+    pub children: Vec<ResultsNode>,
+}
+
+// Whether the district is the scope itself or lies below it, every district is within the nationwide scope
+fn is_within_district(
+    district_parents: &BTreeMap<u64, Option<u64>>,
+    mut district_id: Option<u64>,
+    scope: Option<u64>,
+) -> bool {
+    loop {
+        if district_id == scope {
+            return true;
+        }
+        match district_id {
+            Some(id) => district_id = district_parents.get(&id).copied().flatten(),
+            None => return false,
+        }
+    }
+}
+
+impl ResultsNode {
+    fn aggregate(
+        district_id: Option<u64>,
+        district_votes: &BTreeMap<u64, Vec<u64>>,
+        district_parents: &BTreeMap<u64, Option<u64>>,
+        candidates_count: usize,
+    ) -> Self {
+        let children: Vec<ResultsNode> = district_parents
+            .iter()
+            .filter(|(_, parent_id)| **parent_id == district_id)
+            .map(|(id, _)| {
+                Self::aggregate(
+                    Some(*id),
+                    district_votes,
+                    district_parents,
+                    candidates_count,
+                )
+            })
+            .collect();
+
+        let mut votes_yes = district_id
+            .and_then(|district_id| district_votes.get(&district_id).cloned())
+            .unwrap_or_else(|| vec![0; candidates_count]);
+        for child in &children {
+            for (votes, child_votes) in votes_yes.iter_mut().zip(&child.votes_yes) {
+                *votes += child_votes;
+            }
+        }
+
+        Self {
+            district_id,
+            votes_yes,
+            certified_by: Vec::default(),
+            certified: false,
+            children,
+        }
+    }
+
+    fn find_mut(&mut self, district_id: Option<u64>) -> Option<&mut ResultsNode> {
+        if self.district_id == district_id {
+            return Some(self);
+        }
+        self.children
+            .iter_mut()
+            .find_map(|child| child.find_mut(district_id))
+    }
 }
 
 #[derive(CandidType, Deserialize, Clone)]
@@ -109,10 +719,25 @@ pub struct PresidentialElectionsProposeCandidType {
     pub id: usize,
     pub creator: Principal,
     pub proposal_content: Vec<String>,
+    pub candidates: Vec<Option<Principal>>,
     pub created_at: u64,
     pub state: VoteState,
     pub votes_yes: Vec<u64>,
-    pub voters: Vec<Principal>,
+    pub voters_count: usize,
+    pub winner: Option<usize>,
+    pub tie_break: Option<TieBreak>,
+    pub parent_id: Option<usize>,
+    pub round: u32,
+    pub weighted: bool,
+    pub delegated_votes: Vec<u64>,
+    pub district_id: Option<u64>,
+    pub district_votes: Vec<DistrictVotes>,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct DistrictVotes {
+    pub district_id: u64,
+    pub votes_yes: Vec<u64>,
 }
 
 impl PresidentialElectionsProposeCandidType {
@@ -123,55 +748,224 @@ impl PresidentialElectionsProposeCandidType {
             created_at: vote.created_at,
             state: vote.state.clone(),
             votes_yes: vote.votes_yes.clone(),
-            voters: vote.voters.clone(),
+            voters_count: vote.voters_count,
             proposal_content: vote.proposal_content.clone(),
+            candidates: vote.candidates.clone(),
+            winner: vote.winner,
+            tie_break: vote.tie_break.clone(),
+            parent_id: vote.parent_id,
+            round: vote.round,
+            weighted: vote.weighted,
+            delegated_votes: vote.delegated_votes.clone(),
+            district_id: vote.district_id,
+            district_votes: vote
+                .district_votes
+                .iter()
+                .map(|(district_id, votes_yes)| DistrictVotes {
+                    district_id: *district_id,
+                    votes_yes: votes_yes.clone(),
+                })
+                .collect(),
         }
     }
 }
 
-pub struct PresidentialElectionsProposals(Vec<PresidentialElectionsPropose>);
+pub struct PresidentialElectionsProposals {
+    proposals: Vec<PresidentialElectionsPropose>,
+    // Candidate chosen by every direct voter
+    ballots: VoterBallots<CastVote<usize>>,
+}
 
 impl PresidentialElectionsProposals {
-    pub fn default() -> Self {
-        Self(Vec::default())
+    pub fn init(ballots_memory: Memory) -> Self {
+        Self {
+            proposals: Vec::default(),
+            ballots: VoterBallots::init(ballots_memory),
+        }
     }
 
-    fn next_id(&self) -> usize {
-        self.0.len()
+    pub fn take_proposals(&mut self) -> Vec<PresidentialElectionsPropose> {
+        std::mem::take(&mut self.proposals)
+    }
+
+    pub fn restore_proposals(
+        &mut self,
+        config: &Config,
+        proposals: Vec<PresidentialElectionsPropose>,
+    ) {
+        rearm_timers(
+            &proposals,
+            |propose| {
+                (propose.state == VoteState::Open).then_some((
+                    propose.id,
+                    propose.created_at + config.committee_proposals_duration,
+                ))
+            },
+            close_presidential_elections,
+        );
+        self.proposals = proposals;
+    }
+
+    pub fn next_id(&self) -> usize {
+        self.proposals.len()
     }
 
     pub fn get(&self) -> Vec<PresidentialElectionsProposeCandidType> {
-        self.0
+        self.proposals
+            .iter()
+            .map(PresidentialElectionsProposeCandidType::new)
+            .collect()
+    }
+
+    pub fn get_open(&self) -> Vec<PresidentialElectionsProposeCandidType> {
+        self.proposals
             .iter()
-            .map(|vote| PresidentialElectionsProposeCandidType::new(vote))
+            .filter(|propose| matches!(propose.state, VoteState::Open))
+            .map(PresidentialElectionsProposeCandidType::new)
             .collect()
     }
 
+    pub fn get_by_id(
+        &self,
+        id: usize,
+    ) -> Result<PresidentialElectionsProposeCandidType, ContractError> {
+        self.proposals
+            .get(id)
+            .map(PresidentialElectionsProposeCandidType::new)
+            .ok_or(ContractError::ProposeNotFound { id })
+    }
+
+    pub fn get_page(
+        &self,
+        filter: &ProposeFilter,
+        cursor: Option<usize>,
+        limit: usize,
+    ) -> Page<PresidentialElectionsProposeCandidType> {
+        paginate(
+            &self.proposals,
+            cursor,
+            limit,
+            |propose| filter.matches(&propose.state, &propose.creator, propose.created_at),
+            PresidentialElectionsProposeCandidType::new,
+        )
+    }
+
+    pub fn get_history(
+        &self,
+        id: usize,
+    ) -> Result<Vec<PresidentialElectionsProposeCandidType>, ContractError> {
+        let mut round = self
+            .proposals
+            .get(id)
+            .ok_or(ContractError::ProposeNotFound { id })?;
+
+        while let Some(parent_id) = round.parent_id {
+            round = &self.proposals[parent_id];
+        }
+
+        let mut history = vec![PresidentialElectionsProposeCandidType::new(round)];
+        while let Some(next_round) = self
+            .proposals
+            .iter()
+            .find(|propose| propose.parent_id == Some(round.id))
+        {
+            history.push(PresidentialElectionsProposeCandidType::new(next_round));
+            round = next_round;
+        }
+
+        Ok(history)
+    }
+
     pub fn create_proposal(
         &mut self,
         config: Config,
         creator: Principal,
-        proposal_content: &Vec<String>,
+        proposal_content: &[String],
+        candidates: Vec<Option<Principal>>,
+        weighted: bool,
+        district_id: Option<u64>,
+    ) {
+        self.create_round(
+            config,
+            creator,
+            (proposal_content, candidates),
+            (weighted, district_id),
+            None,
+            1,
+        )
+    }
+
+    // Candidates of the runoff are given by their indexes in the parent round
+    fn create_runoff(&mut self, config: Config, parent_id: usize, runoff: &[usize]) {
+        let (creator, proposal_content, candidates, weighted, district_id, round) = {
+            let parent = &self.proposals[parent_id];
+            (
+                parent.creator,
+                parent.candidates_names(runoff),
+                runoff
+                    .iter()
+                    .map(|index| parent.candidates[*index])
+                    .collect(),
+                parent.weighted,
+                parent.district_id,
+                parent.round + 1,
+            )
+        };
+
+        self.create_round(
+            config,
+            creator,
+            (&proposal_content, candidates),
+            (weighted, district_id),
+            Some(parent_id),
+            round,
+        )
+    }
+
+    // Every round of an election shares its weighting and district scope
+    fn create_round(
+        &mut self,
+        config: Config,
+        creator: Principal,
+        (proposal_content, candidates): (&[String], Vec<Option<Principal>>),
+        (weighted, district_id): (bool, Option<u64>),
+        parent_id: Option<usize>,
+        round: u32,
     ) {
         let id = self.next_id();
 
         let interval = std::time::Duration::from_nanos(config.committee_proposals_duration);
-        let _timer_id = ic_cdk_timers::set_timer(interval, move || {
+        ic_cdk_timers::set_timer(interval, move || {
             close_presidential_elections(id);
         });
 
         let votes: Vec<u64> = proposal_content.iter().map(|_| 0).collect();
 
-        self.0.push(PresidentialElectionsPropose {
+        self.proposals.push(PresidentialElectionsPropose {
             id: self.next_id(),
             creator,
-            _timer_id,
-            proposal_content: proposal_content.clone(),
+            proposal_content: proposal_content.to_vec(),
+            candidates,
             created_at: ic_cdk::api::time(),
             state: VoteState::Open,
             votes_yes: votes,
-            voters: Vec::default(),
-        })
+            winner: None,
+            tie_break: None,
+            parent_id,
+            round,
+            weighted,
+            voters_count: 0,
+            delegated_votes: vec![0; proposal_content.len()],
+            district_id,
+            district_votes: BTreeMap::default(),
+            results: None,
+        });
+
+        record_event(EventKind::ProposeCreated {
+            kind: ProposeKind::PresidentialElections,
+            id,
+            creator,
+        });
     }
 
     pub fn close_proposal(
@@ -179,343 +973,2666 @@ impl PresidentialElectionsProposals {
         config: Config,
         id: usize,
         users_count: usize,
-    ) -> Result<(), String> {
-        let (creator, new_propose) = {
+        total_voting_weight: u64,
+        delegated: &[(Principal, u64, Option<u64>)],
+        district_parents: &BTreeMap<u64, Option<u64>>,
+    ) -> Result<(), ContractError> {
+        let new_propose = {
             let propose = self
-                .0
+                .proposals
                 .get_mut(id)
-                .ok_or(ContractError::ProposeNotFound.to_string())?;
+                .ok_or(ContractError::ProposeNotFound { id })?;
 
-            if ic_cdk::api::time() <= propose.created_at + config.committee_proposals_duration {
-                return Err(ContractError::ProposeInProgress.to_string());
+            let closes_at = propose.created_at + config.committee_proposals_duration;
+            if ic_cdk::api::time() <= closes_at {
+                return Err(ContractError::ProposeInProgress { id, closes_at });
             }
 
+            propose.count_delegated_votes(&self.ballots, delegated, district_parents);
+            propose.results = Some(propose.aggregate_results(district_parents));
+
+            let max_yes = *propose.votes_yes.iter().max().unwrap_or(&0);
+            let leaders = propose.candidates_with_votes(max_yes);
+
             if propose.proposal_content.len() <= 2 {
-                let mut content = propose.votes_yes.iter();
-                let first_item = content.next();
-                let second_item = content.next();
-
-                if (propose.proposal_content.len() == 1)
-                    || (propose.proposal_content.len() == 2 && first_item != second_item)
-                {
-                    propose.state = VoteState::Accepted;
-                    return Ok(());
+                propose.settle_winner(&config, leaders)
+            } else {
+                let electorate = if propose.weighted {
+                    total_voting_weight
+                } else {
+                    users_count as u64
+                };
+                // No electorate, e.g. a district without active users, leaves no majority to reach
+                match max_yes.saturating_mul(10_000).checked_div(electorate) {
+                    None => {
+                        propose.state = VoteState::Unresolved;
+                        println!(
+                            "Presidential vote with id: {:?} has no electorate and is {:?}",
+                            propose.id, propose.state
+                        );
+                        None
+                    }
+                    Some(percent_of_yes_votes) => {
+                        // Weights changed after the vote can lift the votes above the electorate, the share stays at most 100%
+                        let percent_of_yes_votes = percent_of_yes_votes.min(10_000) as u16;
+                        let runoff =
+                            if percent_of_yes_votes >= config.presidential_elections_threshold {
+                                propose.settle_winner(&config, leaders)
+                            } else {
+                                propose.select_runoff(&config)
+                            };
+                        println!("Presidential vote with id: {:?} has been {:?}. This vote received {:?} percent of the votes", propose.id, propose.state, percent_of_yes_votes);
+
+                        runoff
+                    }
                 }
-                propose.state = VoteState::Unresolved;
-                return Ok(());
             }
+        };
 
-            let max_yes = *propose.votes_yes.iter().max().unwrap_or(&0) as usize;
-            let percent_of_yes_votes = ((max_yes * 100_00) / users_count) as u16;
+        record_event(EventKind::ProposeClosed {
+            kind: ProposeKind::PresidentialElections,
+            id,
+            state: self.proposals[id].state.clone(),
+        });
 
-            if percent_of_yes_votes >= config.presidential_elections_threshold {
-                propose.state = VoteState::Accepted;
-                println!("Presidential vote with id: {:?} has been {:?}. This vote received {:?} percent of the votes", propose.id, propose.state, percent_of_yes_votes);
-                return Ok(());
-            }
+        if let Some(new_propose) = new_propose {
+            self.create_runoff(config, id, &new_propose);
+        }
 
-            let mut sorted_votes: Vec<_> = propose.votes_yes.iter().enumerate().collect();
-            sorted_votes.sort_by(|(_, votes), (_, next_votes)| next_votes.cmp(votes));
+        Ok(())
+    }
 
-            let indexes_of_two_latest: Vec<_> = sorted_votes
-                .iter()
-                .take(2)
-                .map(|(index, _)| {
-                    propose
-                        .proposal_content
-                        .get(*index)
-                        .expect("Propose do not exist!?")
-                        .clone()
-                })
-                .collect();
+    pub fn get_voters(&self, id: usize) -> Result<Vec<Principal>, ContractError> {
+        self.proposals
+            .get(id)
+            .ok_or(ContractError::ProposeNotFound { id })?;
 
-            propose.state = VoteState::Unresolved;
-            println!("Presidential vote with id: {:?} has been {:?}. This vote received {:?} percent of the votes", propose.id, propose.state, percent_of_yes_votes);
+        Ok(self.ballots.voters(id))
+    }
 
-            (propose.creator, indexes_of_two_latest)
-        };
-        self.create_proposal(config, creator, &new_propose);
+    pub fn has_voted(&self, id: usize, voter: Principal) -> Result<bool, ContractError> {
+        self.proposals
+            .get(id)
+            .ok_or(ContractError::ProposeNotFound { id })?;
 
-        Ok(())
+        Ok(self.ballots.contains(id, voter))
     }
 
-    pub fn vote(
+    pub fn get_results(&self, id: usize) -> Result<ResultsNode, ContractError> {
+        self.proposals
+            .get(id)
+            .ok_or(ContractError::ProposeNotFound { id })?
+            .results
+            .clone()
+            .ok_or(ContractError::ResultsNotAvailable { id })
+    }
+
+    // `committee_size` is the size of the committee of the certified level
+    pub fn certify_results(
         &mut self,
-        voter: Principal,
-        propose_id: usize,
-        candidate_index: &usize,
-    ) -> Result<(), String> {
-        let propose = self
-            .0
+        config: &Config,
+        id: usize,
+        (district_id, committee_size): (Option<u64>, u64),
+        certifier: Principal,
+    ) -> Result<(), ContractError> {
+        let node = self
+            .proposals
+            .get_mut(id)
+            .ok_or(ContractError::ProposeNotFound { id })?
+            .results
+            .as_mut()
+            .ok_or(ContractError::ResultsNotAvailable { id })?
+            .find_mut(district_id)
+            .ok_or(ContractError::NotInCertifyingCommittee {
+                id,
+                district_id,
+                principal: certifier,
+            })?;
+
+        if node.certified_by.contains(&certifier) {
+            return Err(ContractError::ResultsAlreadyCertified {
+                id,
+                district_id,
+                principal: certifier,
+            });
+        }
+        node.certified_by.push(certifier);
+
+        let percent_of_certifications =
+            (node.certified_by.len() as u64 * 10_000) / committee_size.max(1);
+        node.certified = percent_of_certifications >= config.committee_threshold as u64;
+
+        record_event(EventKind::ResultsCertified {
+            id,
+            district_id,
+            certifier,
+        });
+
+        Ok(())
+    }
+
+    pub fn get_district_id(&self, id: usize) -> Result<Option<u64>, ContractError> {
+        Ok(self
+            .proposals
+            .get(id)
+            .ok_or(ContractError::ProposeNotFound { id })?
+            .district_id)
+    }
+
+    pub fn get_awaiting_lot(&self) -> Vec<usize> {
+        self.proposals
+            .iter()
+            .map(|propose| propose.id)
+            .filter(|id| self.awaits_lot(*id))
+            .collect()
+    }
+
+    pub fn awaits_lot(&self, id: usize) -> bool {
+        self.proposals
+            .get(id)
+            .and_then(|propose| propose.tie_break.as_ref())
+            .is_some_and(|tie_break| {
+                tie_break.is_pending(TieBreakPolicy::Lot) && tie_break.seed.is_none()
+            })
+    }
+
+    pub fn resolve_tie_by_lot(
+        &mut self,
+        config: Config,
+        id: usize,
+        seed: Vec<u8>,
+    ) -> Result<(), ContractError> {
+        let new_propose = {
+            let propose = self
+                .proposals
+                .get_mut(id)
+                .ok_or(ContractError::ProposeNotFound { id })?;
+
+            let tie_break = propose
+                .tie_break
+                .as_ref()
+                .filter(|tie_break| {
+                    tie_break.is_pending(TieBreakPolicy::Lot) && tie_break.seed.is_none()
+                })
+                .ok_or(ContractError::TieBreakNotPending { id })?;
+
+            let (qualified, seats) = propose.tie_break_seats(tie_break);
+            let chosen = draw_lots(&seed, &tie_break.tied_candidates, seats);
+
+            if let Some(tie_break) = propose.tie_break.as_mut() {
+                tie_break.seed = Some(seed);
+            }
+
+            record_event(EventKind::TieBroken {
+                id,
+                chosen: chosen.clone(),
+            });
+            propose.apply_tie_break(qualified, chosen)
+        };
+
+        if let Some(new_propose) = new_propose {
+            self.create_runoff(config, id, &new_propose);
+        }
+
+        Ok(())
+    }
+
+    pub fn resolve_tie_by_committee(
+        &mut self,
+        config: Config,
+        id: usize,
+        candidates: &[usize],
+    ) -> Result<(), ContractError> {
+        let new_propose = {
+            let propose = self
+                .proposals
+                .get_mut(id)
+                .ok_or(ContractError::ProposeNotFound { id })?;
+
+            let tie_break = propose
+                .tie_break
+                .as_ref()
+                .filter(|tie_break| tie_break.is_pending(TieBreakPolicy::CommitteeDecision))
+                .ok_or(ContractError::TieBreakNotPending { id })?;
+
+            if let Some(candidate) = candidates
+                .iter()
+                .find(|candidate| !tie_break.tied_candidates.contains(candidate))
+            {
+                return Err(ContractError::CandidateNotTied {
+                    candidate: *candidate,
+                });
+            }
+
+            let (qualified, seats) = propose.tie_break_seats(tie_break);
+            let mut chosen = candidates.to_vec();
+            chosen.sort();
+            chosen.dedup();
+
+            if chosen.len() != seats || candidates.len() != seats {
+                return Err(ContractError::InvalidTieBreakChoice {
+                    expected: seats,
+                    actual: chosen.len(),
+                });
+            }
+
+            record_event(EventKind::TieBroken {
+                id,
+                chosen: candidates.to_vec(),
+            });
+            propose.apply_tie_break(qualified, candidates.to_vec())
+        };
+
+        if let Some(new_propose) = new_propose {
+            self.create_runoff(config, id, &new_propose);
+        }
+
+        Ok(())
+    }
+
+    pub fn vote(
+        &mut self,
+        voter: Voter,
+        propose_id: usize,
+        candidate_index: &usize,
+        voting_weight: u64,
+        // District of the voter and the districts tree its eligibility is checked against
+        (voter_district_id, district_parents): (Option<u64>, &BTreeMap<u64, Option<u64>>),
+        allow_revoting: bool,
+    ) -> Result<(), ContractError> {
+        let propose = self
+            .proposals
             .iter_mut()
             .find(|propose| propose.id == propose_id)
-            .ok_or(ContractError::ProposeNotFound.to_string())?;
+            .ok_or(ContractError::ProposeNotFound { id: propose_id })?;
 
         if propose.state != VoteState::Open {
-            return Err(ContractError::VoteNotOpen.to_string());
+            return Err(ContractError::VoteNotOpen {
+                id: propose_id,
+                state: propose.state.clone(),
+            });
+        }
+        if let Some(district_id) = propose.district_id.filter(|district_id| {
+            !is_within_district(district_parents, voter_district_id, Some(*district_id))
+        }) {
+            return Err(ContractError::NotInDistrict {
+                id: propose_id,
+                district_id,
+            });
         }
-        if propose.voters.contains(&voter) {
-            return Err(ContractError::UserAlreadyVoted.to_string());
+        if self.ballots.contains(propose_id, voter.entry_identity) && !allow_revoting {
+            return Err(ContractError::UserAlreadyVoted {
+                id: propose_id,
+                principal: voter.identity,
+            });
+        }
+        if *candidate_index >= propose.votes_yes.len() {
+            return Err(ContractError::CandidatesNotFound {
+                index: *candidate_index,
+                candidates_count: propose.votes_yes.len(),
+            });
         }
 
-        let votes = propose
-            .votes_yes
-            .get_mut(candidate_index.clone())
-            .ok_or(ContractError::CandidatesNotFound.to_string())?;
-        *votes += 1;
+        let weight = if propose.weighted { voting_weight } else { 1 };
+        let cast_vote = CastVote {
+            choice: *candidate_index,
+            weight,
+            district_id: voter_district_id,
+        };
 
-        propose.voters.push(voter);
+        // Latest ballot replaces the previous one
+        match self
+            .ballots
+            .insert(propose_id, voter.entry_identity, cast_vote)
+        {
+            Some(previous) => propose.untally(&previous),
+            None => propose.voters_count += 1,
+        }
+        propose.tally(*candidate_index, weight, voter_district_id);
 
+        record_event(EventKind::ProposeVoted {
+            kind: ProposeKind::PresidentialElections,
+            id: propose_id,
+            voter: voter.identity,
+        });
         Ok(())
     }
 }
 
-#[derive(CandidType, Deserialize, Clone)]
-pub enum UserProposeVote {
-    PresidentialElections(usize),
+// Caller of a vote, ballots are keyed by the entry identity so a user can not vote again under a new identity
+#[derive(Clone, Copy)]
+pub struct Voter {
+    pub identity: Principal,
+    pub entry_identity: Principal,
 }
 
 #[derive(CandidType, Deserialize, Clone)]
-pub enum UserPropose {
-    PresidentialElections(Vec<String>),
+pub enum BallotQuestion {
+    Referendum(String),
+    CandidateRace(Vec<String>),
 }
 
-impl UserPropose {
+impl BallotQuestion {
     pub fn is_valid(&self) -> bool {
         match &self {
-            UserPropose::PresidentialElections(candidates) => candidates.len() > 0,
+            BallotQuestion::Referendum(question) => !question.is_empty(),
+            BallotQuestion::CandidateRace(candidates) => !candidates.is_empty(),
         }
     }
-}
-
-#[derive(CandidType, Deserialize, Clone)]
-pub enum CommitteeActions {
-    RegisterNewEntryIdentities(Vec<Principal>),
-    PromoteUser(Principal),
-    DemoteUser(Principal),
-    CreateUserPropose(UserPropose),
-}
 
-impl CommitteeActions {
-    pub fn validate(self) -> Result<Self, String> {
-        let is_valid = match &self {
-            CommitteeActions::RegisterNewEntryIdentities(principals) => principals.len() > 0,
-            CommitteeActions::PromoteUser(user) => user != &Principal::anonymous(),
-            CommitteeActions::DemoteUser(user) => user != &Principal::anonymous(),
-            CommitteeActions::CreateUserPropose(propose) => propose.is_valid(),
-        };
+    fn empty_tally(&self) -> Vec<u64> {
+        match &self {
+            // Referendum tally is stored as [yes, no]
+            BallotQuestion::Referendum(_) => vec![0, 0],
+            BallotQuestion::CandidateRace(candidates) => vec![0; candidates.len()],
+        }
+    }
 
-        if !is_valid {
-            return Err(ContractError::InvalidAction.to_string());
+    fn answer_index(&self, answer: &BallotAnswer) -> Option<usize> {
+        match (&self, answer) {
+            (BallotQuestion::Referendum(_), BallotAnswer::Referendum(true)) => Some(0),
+            (BallotQuestion::Referendum(_), BallotAnswer::Referendum(false)) => Some(1),
+            (BallotQuestion::CandidateRace(candidates), BallotAnswer::CandidateRace(index)) => {
+                (*index < candidates.len()).then_some(*index)
+            }
+            _ => None,
         }
+    }
 
-        Ok(self)
+    fn resolve(&self, tally: &[u64]) -> VoteState {
+        match &self {
+            BallotQuestion::Referendum(_) => match tally[0].cmp(&tally[1]) {
+                std::cmp::Ordering::Greater => VoteState::Accepted,
+                std::cmp::Ordering::Less => VoteState::Rejected,
+                std::cmp::Ordering::Equal => VoteState::Unresolved,
+            },
+            BallotQuestion::CandidateRace(_) => {
+                let max_votes = tally.iter().max().unwrap_or(&0);
+                let leaders = tally.iter().filter(|votes| *votes == max_votes).count();
+
+                if leaders == 1 {
+                    VoteState::Accepted
+                } else {
+                    VoteState::Unresolved
+                }
+            }
+        }
     }
 }
 
 #[derive(CandidType, Deserialize, Clone)]
-pub struct CommitteeProposeCandidType {
-    id: usize,
-    creator: Principal,
-    action: CommitteeActions,
-    created_at: u64,
-    state: VoteState,
-    votes_yes: u64,
-    voters: Vec<Principal>,
+pub enum BallotAnswer {
+    Referendum(bool),
+    CandidateRace(usize),
 }
 
-impl CommitteeProposeCandidType {
-    pub fn new(vote: &CommitteePropose) -> Self {
+#[derive(CandidType, Deserialize)]
+pub struct BallotPropose {
+    pub id: usize,
+    pub creator: Principal,
+    pub questions: Vec<BallotQuestion>,
+    pub created_at: u64,
+    pub state: VoteState,
+    pub questions_state: Vec<VoteState>,
+    pub votes: Vec<Vec<u64>>,
+    pub weighted: bool,
+    pub voters_count: usize,
+    pub delegated_votes: Vec<Vec<u64>>,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct BallotProposeCandidType {
+    pub id: usize,
+    pub creator: Principal,
+    pub questions: Vec<BallotQuestion>,
+    pub created_at: u64,
+    pub state: VoteState,
+    pub questions_state: Vec<VoteState>,
+    pub votes: Vec<Vec<u64>>,
+    pub voters_count: usize,
+    pub weighted: bool,
+    pub delegated_votes: Vec<Vec<u64>>,
+}
+
+impl BallotProposeCandidType {
+    pub fn new(vote: &BallotPropose) -> Self {
         Self {
             id: vote.id,
             creator: vote.creator,
-            action: vote.action.clone(),
+            questions: vote.questions.clone(),
             created_at: vote.created_at,
             state: vote.state.clone(),
-            votes_yes: vote.votes_yes,
-            voters: vote.voters.clone(),
+            questions_state: vote.questions_state.clone(),
+            votes: vote.votes.clone(),
+            voters_count: vote.voters_count,
+            weighted: vote.weighted,
+            delegated_votes: vote.delegated_votes.clone(),
         }
     }
 }
 
-#[derive(Clone)]
-pub struct CommitteePropose {
-    id: usize,
-    creator: Principal,
-    action: CommitteeActions,
-    _timer_id: TimerId,
-    created_at: u64,
-    state: VoteState,
-    votes_yes: u64,
-    voters: Vec<Principal>,
+pub struct BallotProposals {
+    proposals: Vec<BallotPropose>,
+    // Answer indexes of every direct voter
+    ballots: VoterBallots<CastVote<Vec<usize>>>,
 }
 
-#[derive(Clone)]
-pub struct CommitteeProposals(Vec<CommitteePropose>);
+impl BallotProposals {
+    pub fn init(ballots_memory: Memory) -> Self {
+        Self {
+            proposals: Vec::default(),
+            ballots: VoterBallots::init(ballots_memory),
+        }
+    }
 
-impl CommitteeProposals {
-    pub fn default() -> Self {
-        Self(Vec::default())
+    pub fn take_proposals(&mut self) -> Vec<BallotPropose> {
+        std::mem::take(&mut self.proposals)
     }
-    pub fn get(&self) -> Vec<CommitteeProposeCandidType> {
-        self.0
-            .iter()
-            .map(|vote| CommitteeProposeCandidType::new(vote))
-            .collect()
+
+    pub fn restore_proposals(&mut self, config: &Config, proposals: Vec<BallotPropose>) {
+        rearm_timers(
+            &proposals,
+            |propose| {
+                (propose.state == VoteState::Open).then_some((
+                    propose.id,
+                    propose.created_at + config.user_proposals_duration,
+                ))
+            },
+            close_ballot,
+        );
+        self.proposals = proposals;
     }
+
     fn next_id(&self) -> usize {
-        self.0.len()
-    }
-    fn execute_proposal(_propose: &mut CommitteePropose) -> Result<(), String> {
-        Ok(match &_propose.action {
-            CommitteeActions::RegisterNewEntryIdentities(identities) => {
-                register_new_entry_identities(identities)
-            }
-            CommitteeActions::PromoteUser(user) => promote_user(user)?,
-            CommitteeActions::DemoteUser(user) => demote_user(user)?,
-            CommitteeActions::CreateUserPropose(propose) => {
-                create_user_propose(propose, _propose.creator)?
-            }
-        })
+        self.proposals.len()
     }
-    pub fn close_proposal(
-        &mut self,
-        config: Config,
-        id: usize,
-        committee_size: usize,
-    ) -> Result<(), String> {
-        let propose = self
-            .0
-            .get_mut(id)
-            .ok_or(ContractError::ProposeNotFound.to_string())?;
-
-        if ic_cdk::api::time() <= propose.created_at + config.committee_proposals_duration {
-            return Err(ContractError::ProposeInProgress.to_string());
-        }
-
-        let max_yes = propose.votes_yes as usize;
-        let percent_of_yes_votes = ((max_yes * 100_00) / committee_size) as u16;
 
-        if percent_of_yes_votes >= config.presidential_elections_threshold {
-            propose.state = VoteState::Accepted;
-            println!("Committee vote with id: {:?} has been {:?}. This vote received {:?} percent of the votes", propose.id, propose.state, percent_of_yes_votes);
-            return Self::execute_proposal(propose);
-        }
+    pub fn get(&self) -> Vec<BallotProposeCandidType> {
+        self.proposals
+            .iter()
+            .map(BallotProposeCandidType::new)
+            .collect()
+    }
 
-        propose.state = VoteState::Rejected;
-        println!("Committee vote with id: {:?} has been {:?}. This vote received {:?} percent of the votes", propose.id, propose.state, percent_of_yes_votes);
-        Ok(())
+    pub fn get_open(&self) -> Vec<BallotProposeCandidType> {
+        self.proposals
+            .iter()
+            .filter(|propose| matches!(propose.state, VoteState::Open))
+            .map(BallotProposeCandidType::new)
+            .collect()
     }
+
     pub fn create_proposal(
         &mut self,
         config: Config,
         creator: Principal,
-        action: CommitteeActions,
-    ) -> usize {
+        questions: &[BallotQuestion],
+        weighted: bool,
+    ) {
         let id = self.next_id();
 
-        let interval = std::time::Duration::from_nanos(config.committee_proposals_duration);
-        let _timer_id = ic_cdk_timers::set_timer(interval, move || {
-            close_committee_proposal(id);
+        let interval = std::time::Duration::from_nanos(config.user_proposals_duration);
+        ic_cdk_timers::set_timer(interval, move || {
+            close_ballot(id);
         });
 
-        self.0.push(CommitteePropose {
+        self.proposals.push(BallotPropose {
             id,
             creator,
-            action,
-            _timer_id,
+            questions: questions.to_vec(),
             created_at: ic_cdk::api::time(),
             state: VoteState::Open,
-            votes_yes: 0,
-            voters: Vec::default(),
+            questions_state: questions.iter().map(|_| VoteState::Open).collect(),
+            votes: questions.iter().map(BallotQuestion::empty_tally).collect(),
+            weighted,
+            voters_count: 0,
+            delegated_votes: questions.iter().map(BallotQuestion::empty_tally).collect(),
         });
 
-        id
+        record_event(EventKind::ProposeCreated {
+            kind: ProposeKind::Ballot,
+            id,
+            creator,
+        });
+    }
+
+    pub fn get_voters(&self, id: usize) -> Result<Vec<Principal>, ContractError> {
+        self.proposals
+            .get(id)
+            .ok_or(ContractError::ProposeNotFound { id })?;
+
+        Ok(self.ballots.voters(id))
+    }
+
+    pub fn has_voted(&self, id: usize, voter: Principal) -> Result<bool, ContractError> {
+        self.proposals
+            .get(id)
+            .ok_or(ContractError::ProposeNotFound { id })?;
+
+        Ok(self.ballots.contains(id, voter))
+    }
+
+    pub fn close_proposal(
+        &mut self,
+        config: Config,
+        id: usize,
+        delegated: &[(Principal, u64, Option<u64>)],
+    ) -> Result<(), ContractError> {
+        let propose = self
+            .proposals
+            .get_mut(id)
+            .ok_or(ContractError::ProposeNotFound { id })?;
+
+        let closes_at = propose.created_at + config.user_proposals_duration;
+        if ic_cdk::api::time() <= closes_at {
+            return Err(ContractError::ProposeInProgress { id, closes_at });
+        }
+
+        for (representative, voting_weight, _) in delegated {
+            if let Some(cast_vote) = self.ballots.get(id, *representative) {
+                let weight = if propose.weighted { *voting_weight } else { 1 };
+                for (question, index) in cast_vote.choice.iter().enumerate() {
+                    propose.votes[question][*index] += weight;
+                    propose.delegated_votes[question][*index] += weight;
+                }
+            }
+        }
+
+        propose.questions_state = propose
+            .questions
+            .iter()
+            .zip(propose.votes.iter())
+            .map(|(question, tally)| question.resolve(tally))
+            .collect();
+
+        // A single unresolved question leaves the ballot unresolved, otherwise it is accepted when any question passed
+        propose.state = if propose
+            .questions_state
+            .iter()
+            .any(|state| state == &VoteState::Unresolved)
+        {
+            VoteState::Unresolved
+        } else if propose
+            .questions_state
+            .iter()
+            .all(|state| state == &VoteState::Rejected)
+        {
+            VoteState::Rejected
+        } else {
+            VoteState::Accepted
+        };
+        println!(
+            "Ballot with id: {:?} has been {:?}. Questions results: {:?}",
+            propose.id, propose.state, propose.questions_state
+        );
+
+        record_event(EventKind::ProposeClosed {
+            kind: ProposeKind::Ballot,
+            id,
+            state: propose.state.clone(),
+        });
+        Ok(())
     }
-    pub fn vote(&mut self, voter: Principal, propose_id: usize) -> Result<(), String> {
+
+    pub fn vote(
+        &mut self,
+        voter: Voter,
+        propose_id: usize,
+        answers: &[BallotAnswer],
+        voting_weight: u64,
+        allow_revoting: bool,
+    ) -> Result<(), ContractError> {
         let propose = self
-            .0
+            .proposals
             .iter_mut()
             .find(|propose| propose.id == propose_id)
-            .ok_or(ContractError::ProposeNotFound.to_string())?;
+            .ok_or(ContractError::ProposeNotFound { id: propose_id })?;
 
         if propose.state != VoteState::Open {
-            return Err(ContractError::VoteNotOpen.to_string());
+            return Err(ContractError::VoteNotOpen {
+                id: propose_id,
+                state: propose.state.clone(),
+            });
+        }
+        if self.ballots.contains(propose_id, voter.entry_identity) && !allow_revoting {
+            return Err(ContractError::UserAlreadyVoted {
+                id: propose_id,
+                principal: voter.identity,
+            });
         }
-        if propose.voters.contains(&voter) {
-            return Err(ContractError::UserAlreadyVoted.to_string());
+        if answers.len() != propose.questions.len() {
+            return Err(ContractError::InvalidBallotAnswers {
+                expected: propose.questions.len(),
+                actual: answers.len(),
+            });
         }
 
-        propose.votes_yes += 1;
-        propose.voters.push(voter);
+        // Every answer is validated before any tally is touched, so a ballot is counted as a whole or not at all
+        let indexes = propose
+            .questions
+            .iter()
+            .zip(answers.iter())
+            .enumerate()
+            .map(|(index, (question, answer))| {
+                question
+                    .answer_index(answer)
+                    .ok_or(ContractError::InvalidBallotAnswer { question: index })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let weight = if propose.weighted { voting_weight } else { 1 };
+        propose
+            .votes
+            .iter_mut()
+            .zip(indexes.iter())
+            .for_each(|(tally, index)| tally[*index] += weight);
+
+        let cast_vote = CastVote {
+            choice: indexes,
+            weight,
+            district_id: None,
+        };
+
+        // Latest ballot replaces the previous one
+        match self
+            .ballots
+            .insert(propose_id, voter.entry_identity, cast_vote)
+        {
+            Some(previous) => propose
+                .votes
+                .iter_mut()
+                .zip(previous.choice.iter())
+                .for_each(|(tally, index)| tally[*index] -= previous.weight),
+            None => propose.voters_count += 1,
+        }
 
+        record_event(EventKind::ProposeVoted {
+            kind: ProposeKind::Ballot,
+            id: propose_id,
+            voter: voter.identity,
+        });
         Ok(())
     }
 }
 
-pub struct Users(Vec<User>);
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DelegationTopic {
+    All,
+    PresidentialElections,
+    Ballot,
+}
 
-impl Users {
+#[derive(CandidType, Deserialize, Clone)]
+pub struct DelegationEdge {
+    pub delegator: Principal,
+    pub delegate: Principal,
+    // Delegation is overridden when the delegator voted on their own
+    pub voted_directly: bool,
+    // First user down the delegation chain who voted directly
+    pub representative: Option<Principal>,
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct Delegations(BTreeMap<Principal, BTreeMap<DelegationTopic, Principal>>);
+
+impl Delegations {
     pub fn default() -> Self {
-        Self(Vec::default())
-    }
-    pub fn len(&self) -> usize {
-        self.0.len()
-    }
-    pub fn push(&mut self, user: User) {
-        self.0.push(user)
+        Self(BTreeMap::default())
     }
-    pub fn is_in_committee(&self, identity: Principal) -> Result<bool, String> {
-        Ok(self
-            .get_user_by_identity(identity)
-            .ok_or(ContractError::UserNotFound.to_string())?
-            .is_in_committee())
-    }
-    pub fn get_mut_user_by_identity(&mut self, caller: Principal) -> Option<&mut User> {
-        self.0
-            .iter_mut()
-            .find(|user| user.get_user_identity() == Some(caller))
+
+    // Delegation on the topic takes precedence over the delegation of all user proposals
+    fn get_delegate(&self, delegator: &Principal, topic: DelegationTopic) -> Option<Principal> {
+        let delegations = self.0.get(delegator)?;
+
+        delegations
+            .get(&topic)
+            .or(delegations.get(&DelegationTopic::All))
+            .copied()
     }
-    fn get_user_by_identity(&self, identity: Principal) -> Option<&User> {
-        self.0.iter().find(|user| {
-            user.get_user_identity()
-                .is_some_and(|iter_identity| iter_identity == identity)
-        })
+
+    fn leads_to(&self, from: Principal, topic: DelegationTopic, target: Principal) -> bool {
+        let mut visited = BTreeSet::new();
+        let mut current = from;
+
+        loop {
+            if current == target {
+                return true;
+            }
+            if !visited.insert(current) {
+                return false;
+            }
+            match self.get_delegate(&current, topic) {
+                Some(delegate) => current = delegate,
+                None => return false,
+            }
+        }
     }
-    pub fn activate_user(
+
+    pub fn delegate(
         &mut self,
-        caller: Principal,
-        identity: Principal,
-        identity_seed: String,
-    ) -> Result<(), String> {
-        if let Some(user) = self
+        delegator: Principal,
+        topic: DelegationTopic,
+        delegate: Principal,
+    ) -> Result<(), ContractError> {
+        let previous = self.0.entry(delegator).or_default().insert(topic, delegate);
+
+        let creates_cycle = [
+            DelegationTopic::PresidentialElections,
+            DelegationTopic::Ballot,
+        ]
+        .into_iter()
+        .filter(|topic| self.get_delegate(&delegator, *topic) == Some(delegate))
+        .any(|topic| self.leads_to(delegate, topic, delegator));
+
+        if creates_cycle {
+            match previous {
+                Some(previous) => {
+                    self.0.entry(delegator).or_default().insert(topic, previous);
+                }
+                None => self.remove(delegator, topic),
+            }
+            return Err(ContractError::DelegationCycle { delegate });
+        }
+
+        Ok(())
+    }
+
+    pub fn revoke(
+        &mut self,
+        delegator: Principal,
+        topic: DelegationTopic,
+    ) -> Result<(), ContractError> {
+        if !self
             .0
+            .get(&delegator)
+            .is_some_and(|delegations| delegations.contains_key(&topic))
+        {
+            return Err(ContractError::DelegationNotFound { topic });
+        }
+
+        self.remove(delegator, topic);
+        Ok(())
+    }
+
+    // Handover keeps the delegations given by and to the user, the new identity was not bound so it has none of its own
+    pub fn transfer(&mut self, identity: Principal, new_identity: Principal) {
+        if let Some(delegations) = self.0.remove(&identity) {
+            self.0.insert(new_identity, delegations);
+        }
+        self.0
+            .values_mut()
+            .flat_map(|delegations| delegations.values_mut())
+            .filter(|delegate| **delegate == identity)
+            .for_each(|delegate| *delegate = new_identity);
+    }
+
+    // Reset identity is unbound, delegations given by and to it are dropped
+    pub fn drop_identity(&mut self, identity: Principal) {
+        self.0.remove(&identity);
+        self.0.values_mut().for_each(|delegations| {
+            delegations.retain(|_, delegate| *delegate != identity);
+        });
+        self.0.retain(|_, delegations| !delegations.is_empty());
+    }
+
+    fn remove(&mut self, delegator: Principal, topic: DelegationTopic) {
+        if let Some(delegations) = self.0.get_mut(&delegator) {
+            delegations.remove(&topic);
+            if delegations.is_empty() {
+                self.0.remove(&delegator);
+            }
+        }
+    }
+
+    pub fn resolve(
+        &self,
+        topic: DelegationTopic,
+        direct_voters: &[Principal],
+    ) -> Vec<DelegationEdge> {
+        let direct_voters: BTreeSet<&Principal> = direct_voters.iter().collect();
+
+        self.0
+            .keys()
+            .filter_map(|delegator| {
+                let delegate = self.get_delegate(delegator, topic)?;
+
+                let mut visited = BTreeSet::new();
+                let mut current = Some(delegate);
+                let representative = loop {
+                    match current {
+                        Some(user) if direct_voters.contains(&user) => break Some(user),
+                        Some(user) if visited.insert(user) => {
+                            current = self.get_delegate(&user, topic)
+                        }
+                        _ => break None,
+                    }
+                };
+
+                Some(DelegationEdge {
+                    delegator: *delegator,
+                    delegate,
+                    voted_directly: direct_voters.contains(delegator),
+                    representative,
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(CandidType, Deserialize)]
+pub struct Candidacy {
+    // Identity of the user running
+    candidate: Principal,
+    name: String,
+    // Identities of the users who signed the nomination
+    signatures: BTreeSet<Principal>,
+}
+
+// Names and principals of candidates who collected enough signatures, in the order they declared
+pub type Nominations = Vec<(String, Principal)>;
+
+#[derive(CandidType, Deserialize)]
+pub struct ElectionsAnnouncement {
+    id: usize,
+    creator: Principal,
+    announced_at: u64,
+    weighted: bool,
+    // Only users of the district run and sign nominations
+    district_id: Option<u64>,
+    candidacies: Vec<Candidacy>,
+    registration_open: bool,
+    // Candidates who reached the required signatures, in ballot order
+    nominated: Vec<Principal>,
+    election_id: Option<usize>,
+}
+
+impl ElectionsAnnouncement {
+    fn check_open(&self, voter_district_id: Option<u64>) -> Result<(), ContractError> {
+        if !self.registration_open {
+            return Err(ContractError::CandidateRegistrationClosed { id: self.id });
+        }
+        if let Some(district_id) = self
+            .district_id
+            .filter(|district_id| voter_district_id != Some(*district_id))
+        {
+            return Err(ContractError::NotInDistrict {
+                id: self.id,
+                district_id,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct CandidacyCandidType {
+    pub candidate: Principal,
+    pub name: String,
+    pub signatures: u64,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct ElectionsAnnouncementCandidType {
+    pub id: usize,
+    pub creator: Principal,
+    pub announced_at: u64,
+    pub weighted: bool,
+    pub district_id: Option<u64>,
+    pub candidacies: Vec<CandidacyCandidType>,
+    pub registration_open: bool,
+    pub nominated: Vec<Principal>,
+    pub election_id: Option<usize>,
+}
+
+impl ElectionsAnnouncementCandidType {
+    pub fn new(announcement: &ElectionsAnnouncement) -> Self {
+        Self {
+            id: announcement.id,
+            creator: announcement.creator,
+            announced_at: announcement.announced_at,
+            weighted: announcement.weighted,
+            district_id: announcement.district_id,
+            candidacies: announcement
+                .candidacies
+                .iter()
+                .map(|candidacy| CandidacyCandidType {
+                    candidate: candidacy.candidate,
+                    name: candidacy.name.clone(),
+                    signatures: candidacy.signatures.len() as u64,
+                })
+                .collect(),
+            registration_open: announcement.registration_open,
+            nominated: announcement.nominated.clone(),
+            election_id: announcement.election_id,
+        }
+    }
+}
+
+// Elections whose candidates register themselves before the vote
+#[derive(Default)]
+pub struct ElectionsAnnouncements {
+    announcements: Vec<ElectionsAnnouncement>,
+}
+
+impl ElectionsAnnouncements {
+    pub fn take_announcements(&mut self) -> Vec<ElectionsAnnouncement> {
+        std::mem::take(&mut self.announcements)
+    }
+
+    pub fn restore_announcements(
+        &mut self,
+        config: &Config,
+        announcements: Vec<ElectionsAnnouncement>,
+    ) {
+        rearm_timers(
+            &announcements,
+            |announcement| {
+                announcement.registration_open.then_some((
+                    announcement.id,
+                    announcement.announced_at + config.candidate_registration_duration,
+                ))
+            },
+            close_candidate_registration,
+        );
+        self.announcements = announcements;
+    }
+
+    pub fn get(&self) -> Vec<ElectionsAnnouncementCandidType> {
+        self.announcements
+            .iter()
+            .map(ElectionsAnnouncementCandidType::new)
+            .collect()
+    }
+
+    pub fn announce(
+        &mut self,
+        config: &Config,
+        creator: Principal,
+        weighted: bool,
+        district_id: Option<u64>,
+    ) -> usize {
+        let id = self.announcements.len();
+
+        let interval = std::time::Duration::from_nanos(config.candidate_registration_duration);
+        ic_cdk_timers::set_timer(interval, move || {
+            close_candidate_registration(id);
+        });
+
+        self.announcements.push(ElectionsAnnouncement {
+            id,
+            creator,
+            announced_at: ic_cdk::api::time(),
+            weighted,
+            district_id,
+            candidacies: Vec::default(),
+            registration_open: true,
+            nominated: Vec::default(),
+            election_id: None,
+        });
+
+        id
+    }
+
+    pub fn declare_candidacy(
+        &mut self,
+        id: usize,
+        candidate: Principal,
+        name: String,
+        voter_district_id: Option<u64>,
+    ) -> Result<(), ContractError> {
+        let announcement = self
+            .announcements
+            .get_mut(id)
+            .ok_or(ContractError::AnnouncementNotFound { id })?;
+        announcement.check_open(voter_district_id)?;
+
+        if announcement
+            .candidacies
+            .iter()
+            .any(|candidacy| candidacy.candidate == candidate)
+        {
+            return Err(ContractError::AlreadyCandidate {
+                id,
+                principal: candidate,
+            });
+        }
+
+        announcement.candidacies.push(Candidacy {
+            candidate,
+            name,
+            signatures: BTreeSet::default(),
+        });
+        Ok(())
+    }
+
+    pub fn sign_nomination(
+        &mut self,
+        id: usize,
+        candidate: Principal,
+        signer: Principal,
+        voter_district_id: Option<u64>,
+    ) -> Result<(), ContractError> {
+        let announcement = self
+            .announcements
+            .get_mut(id)
+            .ok_or(ContractError::AnnouncementNotFound { id })?;
+        announcement.check_open(voter_district_id)?;
+
+        let candidacy = announcement
+            .candidacies
+            .iter_mut()
+            .find(|candidacy| candidacy.candidate == candidate)
+            .ok_or(ContractError::CandidateNotFound { id, candidate })?;
+
+        // Signatures show support of other voters, a candidate backing themselves would lower the bar by one
+        if signer == candidate {
+            return Err(ContractError::OwnNominationSigned { id, candidate });
+        }
+        if !candidacy.signatures.insert(signer) {
+            return Err(ContractError::NominationAlreadySigned {
+                id,
+                candidate,
+                principal: signer,
+            });
+        }
+        Ok(())
+    }
+
+    // Closes the registration, returns the creator, scope and names with principals of the nominated candidates
+    pub fn close_registration(
+        &mut self,
+        config: &Config,
+        id: usize,
+    ) -> Result<(Principal, bool, Option<u64>, Nominations), ContractError> {
+        let announcement = self
+            .announcements
+            .get_mut(id)
+            .ok_or(ContractError::AnnouncementNotFound { id })?;
+        if !announcement.registration_open {
+            return Err(ContractError::CandidateRegistrationClosed { id });
+        }
+        announcement.registration_open = false;
+
+        let nominated: Nominations = announcement
+            .candidacies
+            .iter()
+            .filter(|candidacy| {
+                candidacy.signatures.len() as u64 >= config.nomination_signatures_required
+            })
+            .map(|candidacy| (candidacy.name.clone(), candidacy.candidate))
+            .collect();
+        announcement.nominated = nominated.iter().map(|(_, candidate)| *candidate).collect();
+
+        Ok((
+            announcement.creator,
+            announcement.weighted,
+            announcement.district_id,
+            nominated,
+        ))
+    }
+
+    pub fn set_election_id(&mut self, id: usize, election_id: usize) {
+        if let Some(announcement) = self.announcements.get_mut(id) {
+            announcement.election_id = Some(election_id);
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub enum UserProposeVote {
+    PresidentialElections(usize),
+    Ballot(Vec<BallotAnswer>),
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub enum UserPropose {
+    PresidentialElections {
+        candidates: Vec<String>,
+        // Votes are tallied with the voting weight of each user
+        weighted: bool,
+        district_id: Option<u64>,
+    },
+    Ballot {
+        questions: Vec<BallotQuestion>,
+        weighted: bool,
+    },
+}
+
+impl UserPropose {
+    pub fn is_valid(&self) -> bool {
+        match &self {
+            UserPropose::PresidentialElections { candidates, .. } => !candidates.is_empty(),
+            UserPropose::Ballot { questions, .. } => {
+                !questions.is_empty() && questions.iter().all(BallotQuestion::is_valid)
+            }
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub enum CommitteeActions {
+    RegisterNewEntryIdentities {
+        entry_identities: Vec<Principal>,
+        // Entries not activated by this time are pruned and counted as expired
+        activation_deadline: Option<u64>,
+        district_id: Option<u64>,
+    },
+    // Roll too large for one propose, its entries register themselves with an inclusion proof
+    ApproveVoterRoll {
+        merkle_root: Vec<u8>,
+        activation_deadline: Option<u64>,
+        district_id: Option<u64>,
+    },
+    CreateDistrict {
+        name: String,
+        parent_id: Option<u64>,
+    },
+    // Roles and weights belong to activated users and name them by their identity
+    PromoteUser(Principal),
+    // Identity of the committee member
+    DemoteUser(Principal),
+    // Identity of an activated user outside the committee
+    AppointObserver(Principal),
+    // Identity of the observer
+    DismissObserver(Principal),
+    // Identity of the user and the weight of their votes
+    SetVotingWeight(Principal, u64),
+    // Entry identity, the user lost the identity this propose replaces
+    ResetUserIdentity(Principal),
+    // Entry identity, so users are suspended whether or not they activated an identity
+    SuspendUser(Principal, String),
+    // Entry identity and the reason, revocation can not be undone
+    RevokeUser(Principal, String),
+    // Entry identity of a suspended user
+    ReinstateUser(Principal),
+    CreateUserPropose(UserPropose),
+    // Opens candidate registration, the elections start with the nominated candidates once it closes
+    AnnouncePresidentialElections {
+        weighted: bool,
+        district_id: Option<u64>,
+    },
+    ResolvePresidentialElectionsTie {
+        propose_id: usize,
+        candidates: Vec<usize>,
+    },
+}
+
+impl CommitteeActions {
+    pub fn validate(self) -> Result<Self, ContractError> {
+        let is_valid = match &self {
+            CommitteeActions::RegisterNewEntryIdentities {
+                entry_identities, ..
+            } => !entry_identities.is_empty(),
+            CommitteeActions::ApproveVoterRoll { merkle_root, .. } => merkle_root.len() == 32,
+            CommitteeActions::CreateDistrict { name, .. } => !name.is_empty(),
+            CommitteeActions::PromoteUser(user) => user != &Principal::anonymous(),
+            CommitteeActions::DemoteUser(user)
+            | CommitteeActions::AppointObserver(user)
+            | CommitteeActions::DismissObserver(user) => user != &Principal::anonymous(),
+            CommitteeActions::SetVotingWeight(user, voting_weight) => {
+                user != &Principal::anonymous() && *voting_weight > 0
+            }
+            CommitteeActions::ResetUserIdentity(entry_identity)
+            | CommitteeActions::ReinstateUser(entry_identity) => {
+                entry_identity != &Principal::anonymous()
+            }
+            CommitteeActions::SuspendUser(entry_identity, reason)
+            | CommitteeActions::RevokeUser(entry_identity, reason) => {
+                entry_identity != &Principal::anonymous() && !reason.is_empty()
+            }
+            CommitteeActions::CreateUserPropose(propose) => propose.is_valid(),
+            CommitteeActions::AnnouncePresidentialElections { .. } => true,
+            CommitteeActions::ResolvePresidentialElectionsTie { candidates, .. } => {
+                !candidates.is_empty()
+            }
+        };
+
+        if !is_valid {
+            return Err(ContractError::InvalidAction);
+        }
+
+        Ok(self)
+    }
+    // District the action targets, its committee decides the propose
+    pub fn district_id(&self) -> Option<u64> {
+        match self {
+            CommitteeActions::RegisterNewEntryIdentities { district_id, .. }
+            | CommitteeActions::ApproveVoterRoll { district_id, .. }
+            | CommitteeActions::AnnouncePresidentialElections { district_id, .. }
+            | CommitteeActions::CreateUserPropose(UserPropose::PresidentialElections {
+                district_id,
+                ..
+            }) => *district_id,
+            CommitteeActions::CreateDistrict { parent_id, .. } => *parent_id,
+            _ => None,
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct CommitteeProposeCandidType {
+    id: usize,
+    creator: Principal,
+    action: CommitteeActions,
+    district_id: Option<u64>,
+    created_at: u64,
+    state: VoteState,
+    votes_yes: u64,
+    voters_count: usize,
+}
+
+impl CommitteeProposeCandidType {
+    pub fn new(vote: &CommitteePropose) -> Self {
+        Self {
+            id: vote.id,
+            creator: vote.creator,
+            action: vote.action.clone(),
+            district_id: vote.district_id,
+            created_at: vote.created_at,
+            state: vote.state.clone(),
+            votes_yes: vote.votes_yes,
+            voters_count: vote.voters_count,
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct CommitteePropose {
+    id: usize,
+    creator: Principal,
+    action: CommitteeActions,
+    // Committee deciding the propose, the one outside districts when none
+    district_id: Option<u64>,
+    created_at: u64,
+    state: VoteState,
+    votes_yes: u64,
+    voters_count: usize,
+}
+
+pub struct CommitteeProposals {
+    proposals: Vec<CommitteePropose>,
+    // Committee members who voted yes
+    voters: VoterBallots<()>,
+}
+
+impl CommitteeProposals {
+    pub fn init(voters_memory: Memory) -> Self {
+        Self {
+            proposals: Vec::default(),
+            voters: VoterBallots::init(voters_memory),
+        }
+    }
+    pub fn take_proposals(&mut self) -> Vec<CommitteePropose> {
+        std::mem::take(&mut self.proposals)
+    }
+    pub fn restore_proposals(&mut self, config: &Config, proposals: Vec<CommitteePropose>) {
+        rearm_timers(
+            &proposals,
+            |propose| {
+                (propose.state == VoteState::Open).then_some((
+                    propose.id,
+                    propose.created_at + config.committee_proposals_duration,
+                ))
+            },
+            close_committee_proposal,
+        );
+        self.proposals = proposals;
+    }
+    pub fn get(&self) -> Vec<CommitteeProposeCandidType> {
+        self.proposals
+            .iter()
+            .map(CommitteeProposeCandidType::new)
+            .collect()
+    }
+    pub fn get_open(&self) -> Vec<CommitteeProposeCandidType> {
+        self.proposals
+            .iter()
+            .filter(|propose| matches!(propose.state, VoteState::Open))
+            .map(CommitteeProposeCandidType::new)
+            .collect()
+    }
+    pub fn get_voters(&self, id: usize) -> Result<Vec<Principal>, ContractError> {
+        self.proposals
+            .get(id)
+            .ok_or(ContractError::ProposeNotFound { id })?;
+
+        Ok(self.voters.voters(id))
+    }
+    pub fn has_voted(&self, id: usize, voter: Principal) -> Result<bool, ContractError> {
+        self.proposals
+            .get(id)
+            .ok_or(ContractError::ProposeNotFound { id })?;
+
+        Ok(self.voters.contains(id, voter))
+    }
+    pub fn get_district_id(&self, id: usize) -> Result<Option<u64>, ContractError> {
+        Ok(self
+            .proposals
+            .get(id)
+            .ok_or(ContractError::ProposeNotFound { id })?
+            .district_id)
+    }
+    pub fn get_by_id(&self, id: usize) -> Result<CommitteeProposeCandidType, ContractError> {
+        self.proposals
+            .get(id)
+            .map(CommitteeProposeCandidType::new)
+            .ok_or(ContractError::ProposeNotFound { id })
+    }
+    pub fn get_page(
+        &self,
+        filter: &ProposeFilter,
+        cursor: Option<usize>,
+        limit: usize,
+    ) -> Page<CommitteeProposeCandidType> {
+        paginate(
+            &self.proposals,
+            cursor,
+            limit,
+            |propose| filter.matches(&propose.state, &propose.creator, propose.created_at),
+            CommitteeProposeCandidType::new,
+        )
+    }
+    fn next_id(&self) -> usize {
+        self.proposals.len()
+    }
+    // Every action validates its input before touching any state, so a failed execution leaves nothing half applied
+    fn execute_proposal(_propose: &CommitteePropose) -> Result<(), ContractError> {
+        match &_propose.action {
+            CommitteeActions::RegisterNewEntryIdentities {
+                entry_identities,
+                activation_deadline,
+                district_id,
+            } => {
+                register_new_entry_identities(entry_identities, *activation_deadline, *district_id)?
+            }
+            CommitteeActions::ApproveVoterRoll {
+                merkle_root,
+                activation_deadline,
+                district_id,
+            } => approve_voter_roll(merkle_root, *activation_deadline, *district_id)?,
+            CommitteeActions::CreateDistrict { name, parent_id } => {
+                create_district(name, *parent_id)?
+            }
+            CommitteeActions::PromoteUser(user) => promote_user(user)?,
+            CommitteeActions::DemoteUser(user) => demote_user(user)?,
+            CommitteeActions::AppointObserver(user) => appoint_observer(user)?,
+            CommitteeActions::DismissObserver(user) => dismiss_observer(user)?,
+            CommitteeActions::SetVotingWeight(user, voting_weight) => {
+                set_voting_weight(user, *voting_weight)?
+            }
+            CommitteeActions::ResetUserIdentity(entry_identity) => {
+                reset_user_identity(entry_identity)?
+            }
+            CommitteeActions::SuspendUser(entry_identity, reason) => set_user_status(
+                entry_identity,
+                UserStatus::Suspended {
+                    reason: reason.clone(),
+                },
+            )?,
+            CommitteeActions::RevokeUser(entry_identity, reason) => set_user_status(
+                entry_identity,
+                UserStatus::Revoked {
+                    reason: reason.clone(),
+                },
+            )?,
+            CommitteeActions::ReinstateUser(entry_identity) => {
+                set_user_status(entry_identity, UserStatus::Active)?
+            }
+            CommitteeActions::CreateUserPropose(propose) => {
+                create_user_propose(propose, _propose.creator)?
+            }
+            CommitteeActions::AnnouncePresidentialElections {
+                weighted,
+                district_id,
+            } => announce_presidential_elections(_propose.creator, *weighted, *district_id)?,
+            CommitteeActions::ResolvePresidentialElectionsTie {
+                propose_id,
+                candidates,
+            } => resolve_presidential_elections_tie(*propose_id, candidates)?,
+        }
+        Ok(())
+    }
+    pub fn close_proposal(
+        &mut self,
+        config: Config,
+        id: usize,
+        committee_size: usize,
+    ) -> Result<(), ContractError> {
+        let propose = self
+            .proposals
+            .get_mut(id)
+            .ok_or(ContractError::ProposeNotFound { id })?;
+
+        let closes_at = propose.created_at + config.committee_proposals_duration;
+        if ic_cdk::api::time() <= closes_at {
+            return Err(ContractError::ProposeInProgress { id, closes_at });
+        }
+
+        // A committee emptied while the propose was open can not accept it
+        let max_yes = propose.votes_yes as usize;
+        let percent_of_yes_votes = (max_yes * 10_000)
+            .checked_div(committee_size)
+            .unwrap_or_default() as u16;
+
+        propose.state = if percent_of_yes_votes >= config.presidential_elections_threshold {
+            match Self::execute_proposal(propose) {
+                Ok(()) => {
+                    record_event(EventKind::ProposeExecuted {
+                        id,
+                        action: propose.action.clone(),
+                    });
+                    VoteState::Accepted
+                }
+                Err(reason) => VoteState::Failed {
+                    reason: Box::new(reason),
+                },
+            }
+        } else {
+            VoteState::Rejected
+        };
+        println!("Committee vote with id: {:?} has been {:?}. This vote received {:?} percent of the votes", propose.id, propose.state, percent_of_yes_votes);
+
+        record_event(EventKind::ProposeClosed {
+            kind: ProposeKind::Committee,
+            id,
+            state: propose.state.clone(),
+        });
+        Ok(())
+    }
+    pub fn create_proposal(
+        &mut self,
+        config: Config,
+        creator: Principal,
+        action: CommitteeActions,
+        district_id: Option<u64>,
+    ) -> usize {
+        let id = self.next_id();
+
+        let interval = std::time::Duration::from_nanos(config.committee_proposals_duration);
+        ic_cdk_timers::set_timer(interval, move || {
+            close_committee_proposal(id);
+        });
+
+        self.proposals.push(CommitteePropose {
+            id,
+            creator,
+            action,
+            district_id,
+            created_at: ic_cdk::api::time(),
+            state: VoteState::Open,
+            votes_yes: 0,
+            voters_count: 0,
+        });
+
+        record_event(EventKind::ProposeCreated {
+            kind: ProposeKind::Committee,
+            id,
+            creator,
+        });
+        id
+    }
+    pub fn vote(&mut self, voter: Voter, propose_id: usize) -> Result<(), ContractError> {
+        let propose = self
+            .proposals
             .iter_mut()
-            .find(|user| user.get_user_entry_identity() == caller)
+            .find(|propose| propose.id == propose_id)
+            .ok_or(ContractError::ProposeNotFound { id: propose_id })?;
+
+        if propose.state != VoteState::Open {
+            return Err(ContractError::VoteNotOpen {
+                id: propose_id,
+                state: propose.state.clone(),
+            });
+        }
+        if self.voters.contains(propose_id, voter.entry_identity) {
+            return Err(ContractError::UserAlreadyVoted {
+                id: propose_id,
+                principal: voter.identity,
+            });
+        }
+
+        propose.votes_yes += 1;
+        propose.voters_count += 1;
+        self.voters.insert(propose_id, voter.entry_identity, ());
+
+        record_event(EventKind::ProposeVoted {
+            kind: ProposeKind::Committee,
+            id: propose_id,
+            voter: voter.identity,
+        });
+        Ok(())
+    }
+    pub fn retract_vote(
+        &mut self,
+        voter: Voter,
+        propose_id: usize,
+        allow_revoting: bool,
+    ) -> Result<(), ContractError> {
+        if !allow_revoting {
+            return Err(ContractError::RevotingNotAllowed);
+        }
+
+        let propose = self
+            .proposals
+            .iter_mut()
+            .find(|propose| propose.id == propose_id)
+            .ok_or(ContractError::ProposeNotFound { id: propose_id })?;
+
+        if propose.state != VoteState::Open {
+            return Err(ContractError::VoteNotOpen {
+                id: propose_id,
+                state: propose.state.clone(),
+            });
+        }
+
+        if self
+            .voters
+            .remove(propose_id, voter.entry_identity)
+            .is_none()
+        {
+            return Err(ContractError::UserNotVoted {
+                id: propose_id,
+                principal: voter.identity,
+            });
+        }
+        propose.votes_yes -= 1;
+        propose.voters_count -= 1;
+
+        record_event(EventKind::VoteRetracted {
+            kind: ProposeKind::Committee,
+            id: propose_id,
+            voter: voter.identity,
+        });
+        Ok(())
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Default)]
+// Only active users are counted, so suspended and revoked ones never reach the turnout math
+pub struct UsersStats {
+    active_users: u64,
+    committee_size: u64,
+    total_voting_weight: u64,
+}
+
+impl UsersStats {
+    fn of(user: &User) -> Self {
+        // Observers are not part of the electorate
+        if !user.is_active() || user.is_observer() {
+            return Self::default();
+        }
+        Self {
+            active_users: 1,
+            committee_size: user.is_in_committee() as u64,
+            total_voting_weight: user.get_voting_weight(),
+        }
+    }
+
+    fn apply(&mut self, added: Self, removed: Self) {
+        self.active_users = self.active_users + added.active_users - removed.active_users;
+        self.committee_size = self.committee_size + added.committee_size - removed.committee_size;
+        self.total_voting_weight =
+            self.total_voting_weight + added.total_voting_weight - removed.total_voting_weight;
+    }
+}
+
+impl Storable for UsersStats {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct RegistrationBatch {
+    id: u64,
+    registered_at: u64,
+    activation_deadline: Option<u64>,
+    // Root of an approved voter roll, entries of such batch register themselves with an inclusion proof
+    merkle_root: Option<Vec<u8>>,
+    district_id: Option<u64>,
+    pending: u64,
+    activated: u64,
+    expired: u64,
+}
+
+impl Storable for RegistrationBatch {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct RegistrationBatchCandidType {
+    pub id: u64,
+    pub registered_at: u64,
+    pub activation_deadline: Option<u64>,
+    pub merkle_root: Option<Vec<u8>>,
+    pub district_id: Option<u64>,
+    pub pending: u64,
+    pub activated: u64,
+    pub expired: u64,
+}
+
+impl RegistrationBatchCandidType {
+    pub fn new(batch: &RegistrationBatch) -> Self {
+        Self {
+            id: batch.id,
+            registered_at: batch.registered_at,
+            activation_deadline: batch.activation_deadline,
+            merkle_root: batch.merkle_root.clone(),
+            district_id: batch.district_id,
+            pending: batch.pending,
+            activated: batch.activated,
+            expired: batch.expired,
+        }
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct District {
+    id: u64,
+    name: String,
+    // Region the district belongs to, results of its elections roll up there
+    parent_id: Option<u64>,
+    // Active users of the district, the base of its committee size and of district-scoped elections
+    stats: UsersStats,
+}
+
+impl Storable for District {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Deserialize, Clone, Debug, PartialEq)]
+pub struct DistrictCandidType {
+    pub id: u64,
+    pub name: String,
+    pub parent_id: Option<u64>,
+    pub population: u64,
+    pub committee_size: u64,
+    pub total_voting_weight: u64,
+}
+
+impl DistrictCandidType {
+    pub fn new(district: &District) -> Self {
+        Self {
+            id: district.id,
+            name: district.name.clone(),
+            parent_id: district.parent_id,
+            population: district.stats.active_users,
+            committee_size: district.stats.committee_size,
+            total_voting_weight: district.stats.total_voting_weight,
+        }
+    }
+}
+
+pub struct Users {
+    // Keyed by entry identity, every user has one from the moment of registration
+    users: StableBTreeMap<StorablePrincipal, User, Memory>,
+    // Identity of an activated user mapped to their entry identity
+    identities: StableBTreeMap<StorablePrincipal, StorablePrincipal, Memory>,
+    // Kept up to date on every change so no call has to walk the roll
+    stats: StableCell<UsersStats, Memory>,
+    batches: StableBTreeMap<u64, RegistrationBatch, Memory>,
+    // Entries still awaiting activation keyed by batch, so the expiry visits only its own batch
+    pending_activations: StableBTreeMap<(u64, StorablePrincipal), (), Memory>,
+    districts: StableBTreeMap<u64, District, Memory>,
+}
+
+impl Users {
+    pub fn init(
+        users_memory: Memory,
+        identities_memory: Memory,
+        stats_memory: Memory,
+        batches_memory: Memory,
+        pending_activations_memory: Memory,
+        districts_memory: Memory,
+    ) -> Self {
+        Self {
+            users: StableBTreeMap::init(users_memory),
+            identities: StableBTreeMap::init(identities_memory),
+            stats: StableCell::init(stats_memory, UsersStats::default())
+                .expect("Failed to initialize users stats."),
+            batches: StableBTreeMap::init(batches_memory),
+            pending_activations: StableBTreeMap::init(pending_activations_memory),
+            districts: StableBTreeMap::init(districts_memory),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.users.len() as usize
+    }
+    // Every write of a user goes through here to keep the stats in line with the roll
+    fn store(&mut self, entry_identity: StorablePrincipal, user: User) -> Option<User> {
+        let added = (user.district_id, UsersStats::of(&user));
+        let previous = self.users.insert(entry_identity, user);
+        let removed = previous
+            .as_ref()
+            .map(|previous| (previous.district_id, UsersStats::of(previous)))
+            .unwrap_or_default();
+
+        self.apply_stats(added, removed);
+        previous
+    }
+    fn remove(&mut self, entry_identity: StorablePrincipal) -> Option<User> {
+        let previous = self.users.remove(&entry_identity)?;
+        self.apply_stats(
+            Default::default(),
+            (previous.district_id, UsersStats::of(&previous)),
+        );
+        Some(previous)
+    }
+    fn apply_stats(
+        &mut self,
+        (added_district_id, added): (Option<u64>, UsersStats),
+        (removed_district_id, removed): (Option<u64>, UsersStats),
+    ) {
+        let mut stats = *self.stats.get();
+        stats.apply(added, removed);
+        self.stats
+            .set(stats)
+            .expect("Failed to update users stats.");
+
+        for (district_id, added, removed) in [
+            (removed_district_id, UsersStats::default(), removed),
+            (added_district_id, added, UsersStats::default()),
+        ] {
+            if let Some(mut district) = district_id.and_then(|id| self.districts.get(&id)) {
+                district.stats.apply(added, removed);
+                self.districts.insert(district.id, district);
+            }
+        }
+    }
+    pub fn create_district(
+        &mut self,
+        name: String,
+        parent_id: Option<u64>,
+    ) -> Result<u64, ContractError> {
+        if let Some(district_id) =
+            parent_id.filter(|district_id| !self.contains_district(*district_id))
         {
-            user.activate(identity, identity_seed);
-            Ok(())
-        } else {
-            Err(ContractError::UserNotFound.to_string())
+            return Err(ContractError::DistrictNotFound { district_id });
         }
+
+        let id = self.districts.len();
+        self.districts.insert(
+            id,
+            District {
+                id,
+                name,
+                parent_id,
+                stats: UsersStats::default(),
+            },
+        );
+        Ok(id)
     }
-    pub fn get_seed_by_entry_identity(&self, identity: Principal) -> Result<String, String> {
-        let user = self
-            .0
+    // Region of every district, the shape of the results tree
+    pub fn get_district_parents(&self) -> BTreeMap<u64, Option<u64>> {
+        self.districts
+            .iter()
+            .map(|(id, district)| (id, district.parent_id))
+            .collect()
+    }
+    // Committee members of the district, or of users outside any district when none is given
+    pub fn get_level_committee_size(&self, district_id: Option<u64>) -> Result<u64, ContractError> {
+        match district_id {
+            Some(district_id) => Ok(self
+                .districts
+                .get(&district_id)
+                .ok_or(ContractError::DistrictNotFound { district_id })?
+                .stats
+                .committee_size),
+            None => {
+                let districts_committee_size: u64 = self
+                    .districts
+                    .iter()
+                    .map(|(_, district)| district.stats.committee_size)
+                    .sum();
+                Ok(self.stats.get().committee_size - districts_committee_size)
+            }
+        }
+    }
+    // Closest level with a committee, districts without one yet are governed by their region or the committee outside districts
+    pub fn get_governing_district(
+        &self,
+        mut district_id: Option<u64>,
+    ) -> Result<Option<u64>, ContractError> {
+        while let Some(id) = district_id {
+            if self.get_level_committee_size(Some(id))? > 0 {
+                break;
+            }
+            district_id = self
+                .districts
+                .get(&id)
+                .and_then(|district| district.parent_id);
+        }
+        Ok(district_id)
+    }
+    pub fn contains_district(&self, district_id: u64) -> bool {
+        self.districts.contains_key(&district_id)
+    }
+    pub fn get_districts(&self) -> Vec<DistrictCandidType> {
+        self.districts
+            .iter()
+            .map(|(_, district)| DistrictCandidType::new(&district))
+            .collect()
+    }
+    // Number of active users and their total voting weight, in the district and every district below it when one is given
+    pub fn get_electorate(&self, district_id: Option<u64>) -> (usize, u64) {
+        let stats = match district_id {
+            Some(district_id) => {
+                let district_parents = self.get_district_parents();
+                self.districts
+                    .iter()
+                    .filter(|(id, _)| {
+                        is_within_district(&district_parents, Some(*id), Some(district_id))
+                    })
+                    .fold(UsersStats::default(), |mut stats, (_, district)| {
+                        stats.apply(district.stats, UsersStats::default());
+                        stats
+                    })
+            }
+            None => *self.stats.get(),
+        };
+        (stats.active_users as usize, stats.total_voting_weight)
+    }
+    pub fn push(&mut self, user: User) {
+        let entry_identity = StorablePrincipal(user.get_user_entry_identity());
+
+        if let Some(identity) = user.get_user_identity() {
+            self.identities
+                .insert(StorablePrincipal(identity), entry_identity);
+        }
+        if let Some(identity) = self
+            .store(entry_identity, user)
+            .and_then(|previous| previous.get_user_identity())
+        {
+            self.identities.remove(&StorablePrincipal(identity));
+        }
+    }
+    // Registers the entries as one batch and returns its id
+    pub fn push_batch(
+        &mut self,
+        entry_identities: &[Principal],
+        activation_deadline: Option<u64>,
+        merkle_root: Option<Vec<u8>>,
+        district_id: Option<u64>,
+    ) -> Result<u64, ContractError> {
+        if let Some(district_id) = district_id.filter(|id| !self.contains_district(*id)) {
+            return Err(ContractError::DistrictNotFound { district_id });
+        }
+        let id = self.batches.len();
+
+        entry_identities.iter().for_each(|entry_identity| {
+            self.push(User::new_in_batch(entry_identity, id, district_id));
+            self.pending_activations
+                .insert((id, StorablePrincipal(*entry_identity)), ());
+        });
+        self.batches.insert(
+            id,
+            RegistrationBatch {
+                id,
+                registered_at: ic_cdk::api::time(),
+                activation_deadline,
+                merkle_root,
+                district_id,
+                pending: entry_identities.len() as u64,
+                activated: 0,
+                expired: 0,
+            },
+        );
+        Ok(id)
+    }
+    // Adds the entry to the roll batch once the proof matches the approved root
+    pub fn register_with_proof(
+        &mut self,
+        entry_identity: Principal,
+        batch_id: u64,
+        proof: &[Vec<u8>],
+    ) -> Result<(), ContractError> {
+        if self.contains_entry_identity(entry_identity) {
+            return Err(ContractError::EntryIdentityAlreadyRegistered { entry_identity });
+        }
+
+        let mut batch = self
+            .batches
+            .get(&batch_id)
+            .ok_or(ContractError::RegistrationBatchNotFound { batch_id })?;
+        let merkle_root = batch
+            .merkle_root
+            .as_ref()
+            .ok_or(ContractError::RegistrationBatchNotFound { batch_id })?;
+
+        if !verify_inclusion(merkle_root, &entry_identity, proof) {
+            return Err(ContractError::InvalidInclusionProof { entry_identity });
+        }
+        if let Some(deadline) = batch
+            .activation_deadline
+            .filter(|deadline| ic_cdk::api::time() > *deadline)
+        {
+            return Err(ContractError::ActivationDeadlinePassed {
+                entry_identity,
+                deadline,
+            });
+        }
+
+        self.push(User::new_in_batch(
+            &entry_identity,
+            batch_id,
+            batch.district_id,
+        ));
+        self.pending_activations
+            .insert((batch_id, StorablePrincipal(entry_identity)), ());
+        batch.pending += 1;
+        self.batches.insert(batch_id, batch);
+        Ok(())
+    }
+    // Prunes entries of the batch that were not activated, returns how many expired
+    pub fn expire_batch(&mut self, batch_id: u64) -> Option<u64> {
+        let mut batch = self.batches.get(&batch_id)?;
+        let pending: Vec<(u64, StorablePrincipal)> = self
+            .pending_activations
+            .range((batch_id, StorablePrincipal(Principal::from_slice(&[])))..)
+            .take_while(|((id, _), _)| *id == batch_id)
+            .map(|(key, _)| key)
+            .collect();
+        let expired = pending.len() as u64;
+
+        for key in pending {
+            self.pending_activations.remove(&key);
+            self.remove(key.1);
+        }
+        batch.pending -= expired;
+        batch.expired += expired;
+        self.batches.insert(batch_id, batch);
+        Some(expired)
+    }
+    // Batches with entries still awaiting activation and the deadline they expire at
+    pub fn get_expiring_batches(&self) -> Vec<(u64, u64)> {
+        self.batches
+            .iter()
+            .filter(|(_, batch)| batch.pending > 0)
+            .filter_map(|(id, batch)| Some((id, batch.activation_deadline?)))
+            .collect()
+    }
+    pub fn get_batches(&self) -> Vec<RegistrationBatchCandidType> {
+        self.batches
             .iter()
-            .find(|user| user.get_user_entry_identity() == identity)
-            .ok_or(ContractError::UserNotFound.to_string())?;
+            .map(|(_, batch)| RegistrationBatchCandidType::new(&batch))
+            .collect()
+    }
+    pub fn is_in_committee(&self, identity: Principal) -> Result<bool, ContractError> {
+        let user = self.get_user_by_identity(identity)?.1;
+        Ok(user.is_in_committee() && user.is_active())
+    }
+    pub fn is_observer(&self, identity: Principal) -> Result<bool, ContractError> {
+        let user = self.get_user_by_identity(identity)?.1;
+        Ok(user.is_observer() && user.is_active())
+    }
+    fn get_active_user_by_identity(
+        &self,
+        identity: Principal,
+    ) -> Result<(StorablePrincipal, User), ContractError> {
+        let (entry_identity, user) = self.get_user_by_identity(identity)?;
+        if !user.is_active() {
+            return Err(ContractError::UserNotActive {
+                entry_identity: entry_identity.0,
+                status: user.get_status().clone(),
+            });
+        }
+        Ok((entry_identity, user))
+    }
+    pub fn get_voter(&self, identity: Principal) -> Result<Voter, ContractError> {
+        let (entry_identity, _) = self.get_user_by_identity(identity)?;
+        Ok(Voter {
+            identity,
+            entry_identity: entry_identity.0,
+        })
+    }
+    pub fn get_identity(&self, entry_identity: Principal) -> Option<Principal> {
+        self.users
+            .get(&StorablePrincipal(entry_identity))?
+            .get_user_identity()
+    }
+    fn get_user_by_identity(
+        &self,
+        identity: Principal,
+    ) -> Result<(StorablePrincipal, User), ContractError> {
+        self.identities
+            .get(&StorablePrincipal(identity))
+            .and_then(|entry_identity| Some((entry_identity, self.users.get(&entry_identity)?)))
+            .ok_or(ContractError::UserNotFound {
+                principal: identity,
+            })
+    }
+    // An activated identity is replaced only when it co-signed the change, or through a committee approved reset followed by the cooldown
+    // Returns the identity handed over
+    pub fn activate_user(
+        &mut self,
+        caller: Principal,
+        identity: Principal,
+        identity_seed: String,
+        identity_reset_cooldown: u64,
+    ) -> Result<Option<Principal>, ContractError> {
+        let entry_identity = StorablePrincipal(caller);
+        let mut user = self
+            .users
+            .get(&entry_identity)
+            .ok_or(ContractError::UserNotFound { principal: caller })?;
+
+        if !user.is_active() {
+            return Err(ContractError::UserNotActive {
+                entry_identity: caller,
+                status: user.get_status().clone(),
+            });
+        }
+        let previous_identity = user.get_user_identity();
+        if previous_identity.is_some() && user.pending_identity != Some(identity) {
+            return Err(ContractError::UserAlreadyActivated {
+                entry_identity: caller,
+            });
+        }
+        if self.contains_identity(identity) {
+            return Err(ContractError::IdentityAlreadyBound { identity });
+        }
+        if let Some(reset_at) = user.identity_reset_at {
+            let available_at = reset_at + identity_reset_cooldown;
+            if ic_cdk::api::time() < available_at {
+                return Err(ContractError::IdentityResetCooldown {
+                    entry_identity: caller,
+                    available_at,
+                });
+            }
+        }
+        if let Some(batch_id) = user.batch_id.filter(|batch_id| {
+            self.pending_activations
+                .contains_key(&(*batch_id, entry_identity))
+        }) {
+            let mut batch = self.batches.get(&batch_id).expect("Batch do not exist!?");
+            if let Some(deadline) = batch
+                .activation_deadline
+                .filter(|deadline| ic_cdk::api::time() > *deadline)
+            {
+                return Err(ContractError::ActivationDeadlinePassed {
+                    entry_identity: caller,
+                    deadline,
+                });
+            }
+            self.pending_activations.remove(&(batch_id, entry_identity));
+            batch.pending -= 1;
+            batch.activated += 1;
+            self.batches.insert(batch_id, batch);
+        }
+
+        if let Some(previous_identity) = previous_identity {
+            self.identities
+                .remove(&StorablePrincipal(previous_identity));
+        }
+        user.activate(identity, identity_seed);
+        self.users.insert(entry_identity, user);
+        self.identities
+            .insert(StorablePrincipal(identity), entry_identity);
+
+        record_event(EventKind::UserActivated {
+            entry_identity: caller,
+            identity,
+        });
+        Ok(previous_identity)
+    }
+    // Returns the entry identity of the account handed over
+    pub fn approve_identity_change(
+        &mut self,
+        identity: Principal,
+        new_identity: Principal,
+    ) -> Result<Principal, ContractError> {
+        if self.contains_identity(new_identity) {
+            return Err(ContractError::IdentityAlreadyBound {
+                identity: new_identity,
+            });
+        }
+
+        let (entry_identity, mut user) = self.get_active_user_by_identity(identity)?;
+        user.pending_identity = Some(new_identity);
+        self.users.insert(entry_identity, user);
+        Ok(entry_identity.0)
+    }
+    // Returns the identity that has been cleared
+    pub fn reset_identity(
+        &mut self,
+        entry_identity: Principal,
+    ) -> Result<Principal, ContractError> {
+        let key = StorablePrincipal(entry_identity);
+        let mut user = self.users.get(&key).ok_or(ContractError::UserNotFound {
+            principal: entry_identity,
+        })?;
+        let identity = user
+            .get_user_identity()
+            .ok_or(ContractError::UserNotActivated { entry_identity })?;
+
+        user.reset_identity(ic_cdk::api::time());
+        self.users.insert(key, user);
+        self.identities.remove(&StorablePrincipal(identity));
+        Ok(identity)
+    }
+    pub fn set_status(
+        &mut self,
+        entry_identity: Principal,
+        status: UserStatus,
+    ) -> Result<(), ContractError> {
+        let key = StorablePrincipal(entry_identity);
+        let mut user = self.users.get(&key).ok_or(ContractError::UserNotFound {
+            principal: entry_identity,
+        })?;
+
+        // Revocation is final, a revoked resident comes back only through a new registration
+        if let UserStatus::Revoked { .. } = user.get_status() {
+            return Err(ContractError::UserNotActive {
+                entry_identity,
+                status: user.get_status().clone(),
+            });
+        }
+
+        user.set_status(status);
+        self.store(key, user);
+        Ok(())
+    }
+    // Committee of a district is capped by the bracket of its population, members without a district by `max_committee_size`
+    pub fn promote(&mut self, config: &Config, identity: Principal) -> Result<(), ContractError> {
+        let (entry_identity, mut user) = self.get_active_user_by_identity(identity)?;
+
+        let committee_size = self.get_level_committee_size(user.district_id)?;
+        let max_committee_size = match user.district_id {
+            Some(district_id) => {
+                config.committee_size_for(self.get_electorate(Some(district_id)).0 as u64)
+            }
+            None => config.max_committee_size,
+        };
+
+        if !user.is_in_committee() {
+            if committee_size >= max_committee_size {
+                return Err(ContractError::CommitteeFull {
+                    district_id: user.district_id,
+                    max_committee_size,
+                });
+            }
+            user.promote();
+            self.store(entry_identity, user);
+        }
+        Ok(())
+    }
+    pub fn demote(&mut self, identity: Principal) -> Result<(), ContractError> {
+        let (entry_identity, mut user) = self.get_user_by_identity(identity)?;
+
+        if user.is_in_committee() {
+            user.demote();
+            self.store(entry_identity, user);
+        }
+        Ok(())
+    }
+    pub fn appoint_observer(&mut self, identity: Principal) -> Result<(), ContractError> {
+        let (entry_identity, mut user) = self.get_active_user_by_identity(identity)?;
+
+        // Leaving the committee is its own decision recorded as a demotion, not a side effect of observing
+        if user.is_in_committee() {
+            return Err(ContractError::CommitteeMemberCannotObserve {
+                principal: identity,
+            });
+        }
+        if !user.is_observer() {
+            user.appoint_observer();
+            self.store(entry_identity, user);
+        }
+        Ok(())
+    }
+    pub fn dismiss_observer(&mut self, identity: Principal) -> Result<(), ContractError> {
+        let (entry_identity, mut user) = self.get_user_by_identity(identity)?;
+
+        if user.is_observer() {
+            user.demote();
+            self.store(entry_identity, user);
+        }
+        Ok(())
+    }
+    pub fn set_voting_weight(
+        &mut self,
+        identity: Principal,
+        voting_weight: u64,
+    ) -> Result<(), ContractError> {
+        let (entry_identity, mut user) = self.get_user_by_identity(identity)?;
+
+        user.set_voting_weight(voting_weight);
+        self.store(entry_identity, user);
+        Ok(())
+    }
+    pub fn get_seed_by_entry_identity(&self, identity: Principal) -> Result<String, ContractError> {
+        let user =
+            self.users
+                .get(&StorablePrincipal(identity))
+                .ok_or(ContractError::UserNotFound {
+                    principal: identity,
+                })?;
 
         Ok(user
             .get_user_seed()
-            .ok_or(ContractError::UserNotFound.to_string())?
+            .ok_or(ContractError::UserNotActivated {
+                entry_identity: identity,
+            })?
             .to_string())
     }
+    pub fn get_district_id(&self, identity: Principal) -> Result<Option<u64>, ContractError> {
+        Ok(self.get_user_by_identity(identity)?.1.get_district_id())
+    }
+    pub fn get_voting_weight(&self, identity: Principal) -> Result<u64, ContractError> {
+        let user = self.get_active_user_by_identity(identity)?.1;
+        if user.is_observer() {
+            return Err(ContractError::ObserverCannotVote {
+                principal: identity,
+            });
+        }
+        Ok(user.get_voting_weight())
+    }
+    pub fn contains_entry_identity(&self, entry_identity: Principal) -> bool {
+        self.users.contains_key(&StorablePrincipal(entry_identity))
+    }
+    pub fn contains_identity(&self, identity: Principal) -> bool {
+        self.identities.contains_key(&StorablePrincipal(identity))
+    }
     pub fn get_committee_size(&self) -> usize {
+        self.stats.get().committee_size as usize
+    }
+}
+
+pub type Memory = VirtualMemory<DefaultMemoryImpl>;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StorablePrincipal(pub Principal);
+
+impl Storable for StorablePrincipal {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Borrowed(self.0.as_slice())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(Principal::from_slice(&bytes))
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 29,
+        is_fixed_size: false,
+    };
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct VoterKey {
+    propose_id: u64,
+    voter: Principal,
+}
+
+impl VoterKey {
+    fn new(propose_id: usize, voter: Principal) -> Self {
+        Self {
+            propose_id: propose_id as u64,
+            voter,
+        }
+    }
+
+    // Sorts before every voter of the propose, the empty principal is the smallest one
+    fn first(propose_id: usize) -> Self {
+        Self::new(propose_id, Principal::from_slice(&[]))
+    }
+}
+
+impl Storable for VoterKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned([&self.propose_id.to_be_bytes()[..], self.voter.as_slice()].concat())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let (propose_id, voter) = bytes.split_at(8);
+        Self {
+            propose_id: u64::from_be_bytes(propose_id.try_into().unwrap()),
+            voter: Principal::from_slice(voter),
+        }
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 8 + 29,
+        is_fixed_size: false,
+    };
+}
+
+// Votes of every propose of one kind, keyed by propose id and the voter's entry identity so a double vote is found without scanning the turnout
+pub struct VoterBallots<T: Storable>(StableBTreeMap<VoterKey, T, Memory>);
+
+impl<T: Storable> VoterBallots<T> {
+    pub fn init(memory: Memory) -> Self {
+        Self(StableBTreeMap::init(memory))
+    }
+    pub fn get(&self, propose_id: usize, voter: Principal) -> Option<T> {
+        self.0.get(&VoterKey::new(propose_id, voter))
+    }
+    pub fn contains(&self, propose_id: usize, voter: Principal) -> bool {
+        self.0.contains_key(&VoterKey::new(propose_id, voter))
+    }
+    pub fn insert(&mut self, propose_id: usize, voter: Principal, value: T) -> Option<T> {
+        self.0.insert(VoterKey::new(propose_id, voter), value)
+    }
+    pub fn remove(&mut self, propose_id: usize, voter: Principal) -> Option<T> {
+        self.0.remove(&VoterKey::new(propose_id, voter))
+    }
+    pub fn voters(&self, propose_id: usize) -> Vec<Principal> {
+        self.0
+            .range(VoterKey::first(propose_id)..VoterKey::first(propose_id + 1))
+            .map(|(key, _)| key.voter)
+            .collect()
+    }
+}
+
+#[derive(CandidType, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ProposeKind {
+    Committee,
+    PresidentialElections,
+    Ballot,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub enum EventKind {
+    ConfigChanged(Config),
+    UserRegistered {
+        entry_identity: Principal,
+        role: Role,
+    },
+    UserActivated {
+        entry_identity: Principal,
+        identity: Principal,
+    },
+    IdentityChangeApproved {
+        entry_identity: Principal,
+        identity: Principal,
+        new_identity: Principal,
+    },
+    UserIdentityReset {
+        entry_identity: Principal,
+        identity: Principal,
+    },
+    VoterRollApproved {
+        batch_id: u64,
+        merkle_root: Vec<u8>,
+    },
+    DistrictCreated {
+        id: u64,
+        name: String,
+        parent_id: Option<u64>,
+    },
+    EntryIdentitiesExpired {
+        batch_id: u64,
+        expired: u64,
+    },
+    UserStatusChanged {
+        entry_identity: Principal,
+        status: UserStatus,
+    },
+    UserPromoted(Principal),
+    UserDemoted(Principal),
+    ObserverAppointed(Principal),
+    ObserverDismissed(Principal),
+    VotingWeightChanged {
+        identity: Principal,
+        voting_weight: u64,
+    },
+    VoteDelegated {
+        delegator: Principal,
+        topic: DelegationTopic,
+        delegate: Principal,
+    },
+    DelegationRevoked {
+        delegator: Principal,
+        topic: DelegationTopic,
+    },
+    ProposeCreated {
+        kind: ProposeKind,
+        id: usize,
+        creator: Principal,
+    },
+    ProposeVoted {
+        kind: ProposeKind,
+        id: usize,
+        voter: Principal,
+    },
+    VoteRetracted {
+        kind: ProposeKind,
+        id: usize,
+        voter: Principal,
+    },
+    ProposeClosed {
+        kind: ProposeKind,
+        id: usize,
+        state: VoteState,
+    },
+    ProposeExecuted {
+        id: usize,
+        action: CommitteeActions,
+    },
+    TieBroken {
+        id: usize,
+        chosen: Vec<usize>,
+    },
+    ElectionsAnnounced {
+        id: usize,
+        creator: Principal,
+    },
+    CandidacyDeclared {
+        id: usize,
+        candidate: Principal,
+        name: String,
+    },
+    NominationSigned {
+        id: usize,
+        candidate: Principal,
+        signer: Principal,
+    },
+    CandidateRegistrationClosed {
+        id: usize,
+        nominated: Vec<Principal>,
+        election_id: Option<usize>,
+    },
+    ResultsCertified {
+        id: usize,
+        district_id: Option<u64>,
+        certifier: Principal,
+    },
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct Event {
+    pub sequence: u64,
+    pub timestamp: u64,
+    pub kind: EventKind,
+    // Hash of the event recorded just before this one, zeroed for the first event
+    pub previous_hash: Vec<u8>,
+    pub hash: Vec<u8>,
+}
+
+impl Event {
+    pub const GENESIS_HASH: [u8; 32] = [0; 32];
+
+    pub fn compute_hash(&self) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        hasher.update(self.sequence.to_be_bytes());
+        hasher.update(self.timestamp.to_be_bytes());
+        hasher.update(&self.previous_hash);
+        hasher.update(Encode!(&self.kind).unwrap());
+        hasher.finalize().to_vec()
+    }
+}
+
+impl Storable for Event {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct Turnout {
+    pub voters_count: usize,
+    // Users entitled to vote on the propose
+    pub electorate: usize,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct PendingProposals {
+    pub committee: Vec<CommitteeProposeCandidType>,
+    pub presidential_elections: Vec<PresidentialElectionsProposeCandidType>,
+    pub ballots: Vec<BallotProposeCandidType>,
+}
+
+#[derive(CandidType, Deserialize, Clone)]
+pub struct EventsHead {
+    pub hash: Vec<u8>,
+    pub count: u64,
+    // Certificate over the head hash, only available in query calls
+    pub certificate: Option<Vec<u8>>,
+}
+
+// Recomputes the chain of events starting at `from` on top of `previous_hash` and returns the hash of the last one,
+// an auditor checks a downloaded log in full from the first event and `Event::GENESIS_HASH`
+pub fn verify_events(
+    from: u64,
+    previous_hash: Vec<u8>,
+    events: impl IntoIterator<Item = Event>,
+) -> Result<Vec<u8>, ContractError> {
+    events
+        .into_iter()
+        .zip(from..)
+        .try_fold(previous_hash, |previous_hash, (event, sequence)| {
+            if event.sequence != sequence
+                || event.previous_hash != previous_hash
+                || event.hash != event.compute_hash()
+            {
+                return Err(ContractError::EventChainBroken { sequence });
+            }
+            Ok(event.hash)
+        })
+}
+
+// Append-only, events are never rewritten or removed once recorded
+pub struct Events(StableLog<Event, Memory, Memory>);
+
+impl Events {
+    pub const MAX_PAGE_SIZE: u64 = 1_000;
+
+    pub fn init(index_memory: Memory, data_memory: Memory) -> Self {
+        Self(StableLog::init(index_memory, data_memory).expect("Failed to initialize event log."))
+    }
+    pub fn len(&self) -> u64 {
+        self.0.len()
+    }
+    pub fn head_hash(&self) -> Vec<u8> {
         self.0
-            .iter()
-            .filter(|user| user.is_in_committee())
-            .collect::<Vec<_>>()
             .len()
+            .checked_sub(1)
+            .and_then(|last| self.0.get(last))
+            .map_or(Event::GENESIS_HASH.to_vec(), |event| event.hash)
+    }
+    pub fn record(&mut self, kind: EventKind) -> Vec<u8> {
+        let mut event = Event {
+            sequence: self.0.len(),
+            timestamp: ic_cdk::api::time(),
+            kind,
+            previous_hash: self.head_hash(),
+            hash: Vec::default(),
+        };
+        event.hash = event.compute_hash();
+
+        self.0
+            .append(&event)
+            .expect("Failed to append event to the log.");
+        event.hash
+    }
+    pub fn get(&self, from: u64, limit: u64) -> Vec<Event> {
+        let to = from
+            .saturating_add(limit.min(Self::MAX_PAGE_SIZE))
+            .min(self.0.len());
+
+        (from..to)
+            .filter_map(|sequence| self.0.get(sequence))
+            .collect()
+    }
+    // Verifies at most a page of events so the query stays within its instruction limit, returns the last hash and the next sequence
+    pub fn verify(
+        &self,
+        from: u64,
+        previous_hash: Vec<u8>,
+        limit: u64,
+    ) -> Result<(Vec<u8>, u64), ContractError> {
+        let to = from
+            .saturating_add(limit.clamp(1, Self::MAX_PAGE_SIZE))
+            .min(self.0.len());
+        let hash = verify_events(
+            from,
+            previous_hash,
+            (from..to).filter_map(|sequence| self.0.get(sequence)),
+        )?;
+        Ok((hash, to.max(from)))
+    }
+}
+
+// Heap state written to stable memory across an upgrade, users, ballots and events live there already
+#[derive(CandidType, Deserialize)]
+pub struct UpgradeState {
+    pub config: Option<Config>,
+    pub committee_proposals: Vec<CommitteePropose>,
+    pub presidential_elections: Vec<PresidentialElectionsPropose>,
+    pub ballots: Vec<BallotPropose>,
+    pub delegations: Delegations,
+    pub announcements: Vec<ElectionsAnnouncement>,
+}
+
+impl Default for UpgradeState {
+    fn default() -> Self {
+        Self {
+            config: None,
+            committee_proposals: Vec::default(),
+            presidential_elections: Vec::default(),
+            ballots: Vec::default(),
+            delegations: Delegations::default(),
+            announcements: Vec::default(),
+        }
+    }
+}
+
+impl Storable for UpgradeState {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(Encode!(self).unwrap())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(bytes.as_ref(), Self).unwrap()
     }
+
+    const BOUND: Bound = Bound::Unbounded;
 }
@@ -0,0 +1,72 @@
+import { expect } from "chai";
+import { Actor, HttpAgent } from "@dfinity/agent";
+import { IDL } from "@dfinity/candid";
+import { canisterId } from "../src/declarations/vote_me_backend";
+import { Config } from "../src/declarations/vote_me_backend/vote_me_backend.did";
+import { deploy } from "../utils/vote_me_backend";
+
+const host =
+  process.env.DFX_NETWORK === "local" ? "http://localhost:4943" : undefined;
+
+// Only built with the `benchmark` feature, so it is not part of the generated declarations
+const getBenchmarks = () =>
+  Actor.createActor<{
+    benchmark_grow_roll: (count: bigint) => Promise<bigint>;
+    benchmark_users_lookups: () => Promise<bigint>;
+  }>(
+    () =>
+      IDL.Service({
+        benchmark_grow_roll: IDL.Func([IDL.Nat64], [IDL.Nat64], []),
+        benchmark_users_lookups: IDL.Func([], [IDL.Nat64], ["query"]),
+      }),
+    { agent: new HttpAgent({ fetch, host }), canisterId }
+  );
+
+// The roll is built a chunk per update call, one call could not fit it in its instruction limit
+const growRoll = async (count: bigint) => {
+  while ((await getBenchmarks().benchmark_grow_roll(count)) < count) {}
+};
+
+describe("Users benchmarks", () => {
+  const config: Config = {
+    committee_proposals_duration: 1n * 1_000n * 1_000n * 1_000n,
+    user_proposals_duration: 1n * 1_000n * 1_000n * 1_000n,
+    max_committee_size: 10n,
+    committee_size_brackets: [],
+    presidential_elections_threshold: 50_01,
+    committee_threshold: 50_01,
+    tie_break_policy: { CommitteeDecision: null },
+    runoff_rules: {
+      max_rounds: 2,
+      expand_tied_runoff: true,
+    },
+    allow_revoting: false,
+    identity_reset_cooldown: 1n * 1_000n * 1_000n * 1_000n,
+    entry_identity_salt: [],
+    candidate_registration_duration: 1n * 1_000n * 1_000n * 1_000n,
+    nomination_signatures_required: 1n,
+  };
+
+  before(() => {
+    deploy(config, []);
+  });
+
+  it("Lookup instructions stay flat as the roll grows", async () => {
+    const counts = [1_000n, 10_000n, 100_000n];
+    const instructions: bigint[] = [];
+    for (const count of counts) {
+      await growRoll(count);
+      instructions.push(await getBenchmarks().benchmark_users_lookups());
+    }
+
+    console.table(
+      counts.map((count, index) => ({
+        users: Number(count),
+        instructions: Number(instructions[index]),
+      }))
+    );
+
+    // Map lookups are logarithmic, a hundred times larger roll may cost at most twice as much
+    expect(Number(instructions[2])).to.be.lessThan(Number(instructions[0]) * 2);
+  });
+});
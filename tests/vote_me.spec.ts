@@ -1,14 +1,23 @@
 import { expect, should, use } from "chai";
 import chaiAsPromised from "chai-as-promised";
-import { getEntryUserIdentity, getUserIdentity } from "../utils/identity";
-import { deploy, getVoteMeBackend } from "../utils/vote_me_backend";
-import { randomBytes, hexlify } from "ethers";
+import {
+  getEntryUserIdentity,
+  getUserIdentity,
+  hashEntryIdentity,
+} from "../utils/identity";
+import { deploy, getVoteMeBackend, unwrap } from "../utils/vote_me_backend";
+import { randomBytes, hexlify, sha256 } from "ethers";
 import { aes_gcm_encrypt, aes_gcm_decrypt } from "../utils/cryptoHelpers";
+import { buildVoterRoll } from "../utils/merkle";
+import { Principal } from "@dfinity/principal";
 import {
+  BallotProposeCandidType,
   CommitteeActions,
   CommitteeProposeCandidType as CommitteePropose,
   Config,
+  Event,
   PresidentialElectionsProposeCandidType,
+  ProposeFilter,
 } from "../src/declarations/vote_me_backend/vote_me_backend.did";
 
 should();
@@ -79,11 +88,18 @@ describe("Vote me", () => {
   // Should depend on: https://sip.lex.pl/akty-prawne/dzu-dziennik-ustaw/kodeks-wyborczy-17679859/dz-2-roz-7
   // Time in nano seconds
   const config: Config = {
-    // 5 people in voting districts of up to 100 people,
-    // 7 people in voting districts from 101 to 300 people,
-    // 9 people in voting districts from 301 to 500 people,
     // 11 people in voting districts with more than 500 people
-    max_committee_size: 5n,
+    // and in the committee of users outside any district
+    max_committee_size: 11n,
+
+    committee_size_brackets: [
+      // 5 people in voting districts of up to 100 people,
+      { max_population: 100n, committee_size: 5n },
+      // 7 people in voting districts from 101 to 300 people,
+      { max_population: 300n, committee_size: 7n },
+      // 9 people in voting districts from 301 to 500 people,
+      { max_population: 500n, committee_size: 9n },
+    ],
 
     // Recommended: 24n * 60n * 60n * 1000n * 1_000n * 1_000n,
     user_proposals_duration: 5n * 1_000n * 1_000n * 1_000n,
@@ -96,6 +112,62 @@ describe("Vote me", () => {
 
     // Recommended: 50_01 (50.01%)
     committee_threshold: 50_01,
+
+    tie_break_policy: { CommitteeDecision: null },
+
+    runoff_rules: {
+      max_rounds: 2,
+      expand_tied_runoff: true,
+    },
+
+    allow_revoting: false,
+
+    // Recommended: 7n * 24n * 60n * 60n * 1_000n * 1_000n * 1_000n,
+    identity_reset_cooldown: 5n * 1_000n * 1_000n * 1_000n,
+
+    // Recommended: [randomBytes(32)], keeps entry identities hashed in the registry
+    entry_identity_salt: [],
+
+    // Recommended: 14n * 24n * 60n * 60n * 1_000n * 1_000n * 1_000n,
+    candidate_registration_duration: 5n * 1_000n * 1_000n * 1_000n,
+
+    // Recommended: 100_000n
+    nomination_signatures_required: 1n,
+  };
+
+  // Activates the entry identity with `principal`, the encrypted seed is returned for later activations
+  const activateUser = async (
+    entry = entryIdentity,
+    principal = identityPrincipal,
+    seed = user1seed
+  ) => {
+    const encryptedSeed = await aes_gcm_encrypt(
+      entry.getKeyPair().publicKey,
+      seed
+    );
+    await getVoteMeBackend(entry)
+      .activate_user(principal, encryptedSeed)
+      .then(unwrap);
+    return encryptedSeed;
+  };
+
+  // The committee member creates every action, votes it through and waits for it to close before the next one
+  const executeCommitteeActions = async (
+    committeeActions: CommitteeActions[],
+    { onExecuted = async (_committeeAction: CommitteeActions) => {} } = {}
+  ) => {
+    for (const committeeAction of committeeActions) {
+      const proposeId = await getVoteMeBackend(identity)
+        .committee_create_propose(committeeAction)
+        .then(unwrap);
+      await getVoteMeBackend(identity)
+        .committee_vote_on_propose(proposeId)
+        .then(unwrap);
+      await sleep(
+        Number(config.committee_proposals_duration / 1_000n / 1_000n)
+      );
+      await onExecuted(committeeAction);
+    }
   };
 
   console.table({
@@ -121,7 +193,174 @@ describe("Vote me", () => {
           identityPrincipal,
           encryptedSeed
         )
-      ).to.be.fulfilled;
+      ).to.eventually.be.deep.eq({ Ok: null });
+    });
+
+    it("User can activate account registered as salted hash", async () => {
+      const salt = randomBytes(32);
+      deploy({ ...config, entry_identity_salt: [salt] }, [
+        hashEntryIdentity(salt, entryIdentityPrincipal),
+      ]);
+
+      const encryptedSeed = await aes_gcm_encrypt(
+        entryIdentity.getKeyPair().publicKey,
+        user1seed
+      );
+
+      await expect(
+        getVoteMeBackend(entryIdentity).activate_user(
+          identityPrincipal,
+          encryptedSeed
+        )
+      ).to.eventually.be.deep.eq({ Ok: null });
+      await expect(
+        getVoteMeBackend(entryIdentity).get_salt()
+      ).to.eventually.be.deep.eq({ Ok: encryptedSeed });
+
+      const kinds = await getVoteMeBackend(identity)
+        .get_events(0n, 100n)
+        .then(unwrap)
+        .then((events: Event[]) => events.map((event) => event.kind));
+      expect(kinds).to.deep.include({
+        UserRegistered: {
+          entry_identity: hashEntryIdentity(salt, entryIdentityPrincipal),
+          role: { Committee: null },
+        },
+      });
+      expect(kinds).not.to.deep.include({
+        UserRegistered: {
+          entry_identity: entryIdentityPrincipal,
+          role: { Committee: null },
+        },
+      });
+
+      const configChanged = kinds.find((kind) => "ConfigChanged" in kind);
+      expect(configChanged).to.not.be.undefined;
+      const loggedSalt = (configChanged as { ConfigChanged: Config })
+        .ConfigChanged.entry_identity_salt[0];
+      expect(hexlify(Uint8Array.from(loggedSalt ?? []))).to.not.be.eq(
+        hexlify(salt)
+      );
+      expect(hexlify(Uint8Array.from(loggedSalt ?? []))).to.be.eq(
+        sha256(salt)
+      );
+    });
+
+    it("User can not activate account twice", async () => {
+      const encryptedSeed = await activateUser();
+
+      await expect(
+        getVoteMeBackend(entryIdentity).activate_user(
+          identitySecondUserPrincipal,
+          encryptedSeed
+        )
+      ).to.eventually.be.deep.eq({
+        Err: {
+          UserAlreadyActivated: { entry_identity: entryIdentityPrincipal },
+        },
+      });
+    });
+
+    it("Active identity can hand the account over to a new identity", async () => {
+      const encryptedSeed = await activateUser();
+
+      await expect(
+        getVoteMeBackend(identity).approve_identity_change(identityPrincipal)
+      ).to.eventually.be.deep.eq({
+        Err: { IdentityAlreadyBound: { identity: identityPrincipal } },
+      });
+
+      await expect(
+        getVoteMeBackend(identity).approve_identity_change(
+          identitySecondUserPrincipal
+        )
+      ).to.eventually.be.deep.eq({ Ok: null });
+
+      await expect(
+        getVoteMeBackend(entryIdentity).activate_user(
+          identitySecondUserPrincipal,
+          encryptedSeed
+        )
+      ).to.eventually.be.deep.eq({ Ok: null });
+
+      await expect(
+        getVoteMeBackend(identity).user_belongs_to_committee()
+      ).to.eventually.be.deep.eq({
+        Err: { UserNotFound: { principal: identityPrincipal } },
+      });
+      await expect(
+        getVoteMeBackend(identitySecondUser).user_belongs_to_committee()
+      ).to.eventually.be.deep.eq({ Ok: true });
+    });
+
+    it("Handed over identity can not vote again", async () => {
+      const encryptedSeed = await activateUser();
+
+      const electionPropose = await getVoteMeBackend(identity)
+        .committee_create_propose({
+          CreateUserPropose: {
+            PresidentialElections: {
+              candidates: ["Jan Kowalski", "Mariusz Broda"],
+              weighted: false,
+              district_id: [],
+            },
+          },
+        })
+        .then(unwrap);
+      await getVoteMeBackend(identity)
+        .committee_vote_on_propose(electionPropose)
+        .then(unwrap);
+      await sleep(
+        Number(config.committee_proposals_duration / 1_000n / 1_000n)
+      );
+
+      const proposeId = await getVoteMeBackend(identity)
+        .committee_create_propose({
+          CreateDistrict: { name: "Warszawa", parent_id: [] },
+        })
+        .then(unwrap);
+      await getVoteMeBackend(identity)
+        .committee_vote_on_propose(proposeId)
+        .then(unwrap);
+      await getVoteMeBackend(identity)
+        .vote_on_propose({ PresidentialElections: 0n }, 0n)
+        .then(unwrap);
+
+      await getVoteMeBackend(identity)
+        .approve_identity_change(identitySecondUserPrincipal)
+        .then(unwrap);
+      await getVoteMeBackend(entryIdentity)
+        .activate_user(identitySecondUserPrincipal, encryptedSeed)
+        .then(unwrap);
+
+      await expect(
+        getVoteMeBackend(identitySecondUser).committee_vote_on_propose(
+          proposeId
+        )
+      ).to.eventually.be.deep.eq({
+        Err: {
+          UserAlreadyVoted: {
+            id: proposeId,
+            principal: identitySecondUserPrincipal,
+          },
+        },
+      });
+      await expect(
+        getVoteMeBackend(identitySecondUser).vote_on_propose(
+          { PresidentialElections: 0n },
+          1n
+        )
+      ).to.eventually.be.deep.eq({
+        Err: {
+          UserAlreadyVoted: { id: 0n, principal: identitySecondUserPrincipal },
+        },
+      });
+      await expect(
+        getVoteMeBackend(identitySecondUser).has_voted(
+          { PresidentialElections: null },
+          0n
+        )
+      ).to.eventually.be.deep.eq({ Ok: true });
     });
 
     it("User can not activate account", async () => {
@@ -142,7 +381,9 @@ describe("Vote me", () => {
           identityPrincipal,
           encryptedSeed
         )
-      ).to.be.rejectedWith("User not found");
+      ).to.eventually.be.deep.eq({
+        Err: { UserNotFound: { principal: entryIdentity.getPrincipal() } },
+      });
     });
 
     it("User get own encrypted salt", async () => {
@@ -156,7 +397,7 @@ describe("Vote me", () => {
 
       await expect(
         getVoteMeBackend(entryIdentity).get_salt()
-      ).to.be.eventually.eq(encryptedSeed);
+      ).to.eventually.be.deep.eq({ Ok: encryptedSeed });
 
       const decryptedSeed = await aes_gcm_decrypt(encryptedSeed, keyPair.secretKey)
       console.log({decryptedSeed, user1seed})
@@ -165,17 +406,11 @@ describe("Vote me", () => {
     });
 
     it("User salt is eq decrypted salt", async () => {
-      const encryptedSeed = await aes_gcm_encrypt(
-        entryIdentity.getKeyPair().publicKey,
-        user1seed
-      );
+      await activateUser();
 
-      await getVoteMeBackend(entryIdentity).activate_user(
-        identityPrincipal,
-        encryptedSeed
-      );
-
-      const salt = await getVoteMeBackend(entryIdentity).get_salt();
+      const salt = await getVoteMeBackend(entryIdentity)
+        .get_salt()
+        .then(unwrap);
 
       identity.getKeyPair();
       salt;
@@ -185,25 +420,22 @@ describe("Vote me", () => {
   describe("Committee can use CommitteeActions", () => {
     describe("Committee 'register entry' identities", () => {
       it("Committee can 'register entry' without identities", async () => {
-        const encryptedSeed = await aes_gcm_encrypt(
-          entryIdentity.getKeyPair().publicKey,
-          user1seed
-        );
-        await getVoteMeBackend(entryIdentity).activate_user(
-          identityPrincipal,
-          encryptedSeed
-        );
+        await activateUser();
 
         const registerNewEntryIdentities = {
-          RegisterNewEntryIdentities: [entryIdentitySecondUserPrincipal],
+          RegisterNewEntryIdentities: {
+            entry_identities: [entryIdentitySecondUserPrincipal],
+            activation_deadline: [],
+            district_id: [],
+          },
         };
         const proposeId = await getVoteMeBackend(
           identity
-        ).committee_create_propose(registerNewEntryIdentities);
+        ).committee_create_propose(registerNewEntryIdentities).then(unwrap);
 
         await expect(
           getVoteMeBackend(identity).committee_vote_on_propose(proposeId)
-        ).to.be.fulfilled;
+        ).to.eventually.be.deep.eq({ Ok: null });
 
         await getVoteMeBackend()
           .get_committee_proposals()
@@ -218,7 +450,8 @@ describe("Vote me", () => {
                 id: 0n,
                 creator: identityPrincipal,
                 action: registerNewEntryIdentities,
-                voters: [identityPrincipal],
+                district_id: [],
+                voters_count: 1n,
                 state: { Open: null },
                 votes_yes: 1n,
               },
@@ -241,73 +474,566 @@ describe("Vote me", () => {
                 id: 0n,
                 creator: identityPrincipal,
                 action: registerNewEntryIdentities,
-                voters: [identityPrincipal],
+                district_id: [],
+                voters_count: 1n,
                 state: { Accepted: null },
                 votes_yes: 1n,
               },
             ]);
           });
-        await getVoteMeBackend(entryIdentitySecondUser).activate_user(
-          identitySecondUserPrincipal,
-          encryptedSeed
+        await activateUser(
+          entryIdentitySecondUser,
+          identitySecondUserPrincipal
         );
 
         await expect(
           getVoteMeBackend(identitySecondUser).user_belongs_to_committee()
-        ).to.eventually.be.eq(false);
+        ).to.eventually.be.deep.eq({ Ok: false });
+      });
+      it("Committee member can not retract vote when revoting is not allowed", async () => {
+        await activateUser();
+
+        const proposeId = await getVoteMeBackend(
+          identity
+        ).committee_create_propose({
+          RegisterNewEntryIdentities: {
+            entry_identities: [entryIdentitySecondUserPrincipal],
+            activation_deadline: [],
+            district_id: [],
+          },
+        }).then(unwrap);
+        await getVoteMeBackend(identity).committee_vote_on_propose(proposeId);
+
+        await expect(
+          getVoteMeBackend(identity).committee_retract_vote(proposeId)
+        ).to.eventually.be.deep.eq({ Err: { RevotingNotAllowed: null } });
       });
       it("Committee can not 'register entry' identities", async () => {
-        const encryptedSeed = await aes_gcm_encrypt(
-          entryIdentity.getKeyPair().publicKey,
-          user1seed
-        );
-        await getVoteMeBackend(entryIdentity).activate_user(
-          identityPrincipal,
-          encryptedSeed
-        );
+        await activateUser();
 
         const registerNewEntryIdentities = {
-          RegisterNewEntryIdentities: [],
+          RegisterNewEntryIdentities: {
+            entry_identities: [],
+            activation_deadline: [],
+            district_id: [],
+          },
         };
 
         await expect(
           getVoteMeBackend(identity).committee_create_propose(
             registerNewEntryIdentities
           )
-        ).to.be.rejectedWith("Invalid action.");
+        ).to.eventually.be.deep.eq({ Err: { InvalidAction: null } });
       });
     });
-    describe("Committee propose 'promote' user", () => {
-      it("Committee can propose 'promote' user", async () => {
-        const encryptedSeed = await aes_gcm_encrypt(
-          entryIdentity.getKeyPair().publicKey,
-          user1seed
+    describe("Committee approve voter roll", () => {
+      it("User registers from the roll with an inclusion proof", async () => {
+        const encryptedSeed = await activateUser();
+
+        const roll = buildVoterRoll([
+          Principal.fromText("aaaaa-aa"),
+          entryIdentitySecondUserPrincipal,
+          Principal.anonymous(),
+        ]);
+        const proposeId = await getVoteMeBackend(identity)
+          .committee_create_propose({
+            ApproveVoterRoll: {
+              merkle_root: roll.root,
+              activation_deadline: [],
+              district_id: [],
+            },
+          })
+          .then(unwrap);
+        await getVoteMeBackend(identity)
+          .committee_vote_on_propose(proposeId)
+          .then(unwrap);
+        await sleep(
+          Number(config.committee_proposals_duration / 1_000n / 1_000n)
         );
-        await getVoteMeBackend(entryIdentity).activate_user(
-          identityPrincipal,
-          encryptedSeed
+
+        await expect(
+          getVoteMeBackend(entryIdentitySecondUser).activate_user_with_proof(
+            0n,
+            roll.proof(Principal.anonymous()),
+            identitySecondUserPrincipal,
+            encryptedSeed
+          )
+        ).to.eventually.be.deep.eq({
+          Err: {
+            InvalidInclusionProof: {
+              entry_identity: entryIdentitySecondUserPrincipal,
+            },
+          },
+        });
+        await expect(
+          getVoteMeBackend(entryIdentitySecondUser).activate_user_with_proof(
+            0n,
+            roll.proof(entryIdentitySecondUserPrincipal),
+            identitySecondUserPrincipal,
+            encryptedSeed
+          )
+        ).to.eventually.be.deep.eq({ Ok: null });
+
+        const batches = await getVoteMeBackend(identity)
+          .get_registration_batches()
+          .then(unwrap);
+        expect(batches[0]).to.include({
+          pending: 0n,
+          activated: 1n,
+          expired: 0n,
+        });
+        await expect(
+          getVoteMeBackend(identitySecondUser).user_belongs_to_committee()
+        ).to.eventually.be.deep.eq({ Ok: false });
+      });
+    });
+
+    describe("Committee create district", () => {
+      it("District users are counted in the district and vote on its elections", async () => {
+        await activateUser();
+
+        const committeeActions: CommitteeActions[] = [
+          { CreateDistrict: { name: "Warszawa", parent_id: [] } },
+          {
+            RegisterNewEntryIdentities: {
+              entry_identities: [entryIdentitySecondUserPrincipal],
+              activation_deadline: [],
+              district_id: [0n],
+            },
+          },
+        ];
+        await executeCommitteeActions(committeeActions);
+
+        await activateUser(
+          entryIdentitySecondUser,
+          identitySecondUserPrincipal
+        );
+        await expect(
+          getVoteMeBackend(identity).get_districts()
+        ).to.eventually.be.deep.eq([
+          {
+            id: 0n,
+            name: "Warszawa",
+            parent_id: [],
+            population: 1n,
+            committee_size: 0n,
+            total_voting_weight: 1n,
+          },
+        ]);
+
+        const proposeId = await getVoteMeBackend(identity)
+          .committee_create_propose({
+            CreateUserPropose: {
+              PresidentialElections: {
+                candidates: ["Jan Kowalski"],
+                weighted: false,
+                district_id: [0n],
+              },
+            },
+          })
+          .then(unwrap);
+        await getVoteMeBackend(identity)
+          .committee_vote_on_propose(proposeId)
+          .then(unwrap);
+        await sleep(
+          Number(config.committee_proposals_duration / 1_000n / 1_000n)
         );
 
-        const encryptedSeed2 = await aes_gcm_encrypt(
-          identitySecondUser.getKeyPair().publicKey,
-          user1seed
+        await expect(
+          getVoteMeBackend(identity).vote_on_propose(
+            { PresidentialElections: 0n },
+            0n
+          )
+        ).to.eventually.be.deep.eq({
+          Err: { NotInDistrict: { id: 0n, district_id: 0n } },
+        });
+        await expect(
+          getVoteMeBackend(identitySecondUser).vote_on_propose(
+            { PresidentialElections: 0n },
+            0n
+          )
+        ).to.eventually.be.deep.eq({ Ok: null });
+      });
+    });
+
+    describe("Committee create elections in an empty district", () => {
+      it("Elections without electorate close as unresolved", async () => {
+        await activateUser();
+
+        const committeeActions: CommitteeActions[] = [
+          { CreateDistrict: { name: "Warszawa", parent_id: [] } },
+          {
+            CreateUserPropose: {
+              PresidentialElections: {
+                candidates: ["Jan Kowalski", "Mariusz Broda", "Andrzej Kłoda"],
+                weighted: false,
+                district_id: [0n],
+              },
+            },
+          },
+        ];
+        await executeCommitteeActions(committeeActions);
+
+        await sleep(Number(config.user_proposals_duration / 1_000n / 1_000n));
+
+        await expect(
+          getVoteMeBackend()
+            .get_presidential_election(0n)
+            .then(unwrap)
+            .then(({ state }) => state)
+        ).to.eventually.be.deep.eq({ Unresolved: null });
+      });
+    });
+
+    describe("District committee", () => {
+      it("Only the district committee decides proposals of its district", async () => {
+        await activateUser();
+
+        // The district has no committee yet, so the committee outside districts decides for it
+        const committeeActions: CommitteeActions[] = [
+          { CreateDistrict: { name: "Warszawa", parent_id: [] } },
+          {
+            RegisterNewEntryIdentities: {
+              entry_identities: [entryIdentitySecondUserPrincipal],
+              activation_deadline: [],
+              district_id: [0n],
+            },
+          },
+          { PromoteUser: identitySecondUserPrincipal },
+        ];
+        await executeCommitteeActions(committeeActions, {
+          onExecuted: async (committeeAction) => {
+            if ("RegisterNewEntryIdentities" in committeeAction) {
+              await activateUser(
+                entryIdentitySecondUser,
+                identitySecondUserPrincipal
+              );
+            }
+          },
+        });
+
+        const districtElections: CommitteeActions = {
+          CreateUserPropose: {
+            PresidentialElections: {
+              candidates: ["Jan Kowalski", "Mariusz Broda"],
+              weighted: false,
+              district_id: [0n],
+            },
+          },
+        };
+        await expect(
+          getVoteMeBackend(identity).committee_create_propose(districtElections)
+        ).to.eventually.be.deep.eq({
+          Err: {
+            NotInDistrictCommittee: {
+              district_id: [0n],
+              principal: identityPrincipal,
+            },
+          },
+        });
+        await expect(
+          getVoteMeBackend(identitySecondUser).committee_create_propose({
+            CreateDistrict: { name: "Kraków", parent_id: [] },
+          })
+        ).to.eventually.be.deep.eq({
+          Err: {
+            NotInDistrictCommittee: {
+              district_id: [],
+              principal: identitySecondUserPrincipal,
+            },
+          },
+        });
+
+        const proposeId = await getVoteMeBackend(identitySecondUser)
+          .committee_create_propose(districtElections)
+          .then(unwrap);
+        await expect(
+          getVoteMeBackend(identity).committee_vote_on_propose(proposeId)
+        ).to.eventually.be.deep.eq({
+          Err: {
+            NotInDistrictCommittee: {
+              district_id: [0n],
+              principal: identityPrincipal,
+            },
+          },
+        });
+        await getVoteMeBackend(identitySecondUser)
+          .committee_vote_on_propose(proposeId)
+          .then(unwrap);
+        await expect(
+          getVoteMeBackend(identity).get_turnout({ Committee: null }, proposeId)
+        ).to.eventually.be.deep.eq({
+          Ok: { voters_count: 1n, electorate: 1n },
+        });
+        await sleep(
+          Number(config.committee_proposals_duration / 1_000n / 1_000n)
+        );
+
+        // One of one district committee members is above the threshold, one of two in total would not be
+        await expect(
+          getVoteMeBackend()
+            .get_committee_proposal(proposeId)
+            .then(unwrap)
+            .then(({ district_id, state }) => ({ district_id, state }))
+        ).to.eventually.be.deep.eq({
+          district_id: [0n],
+          state: { Accepted: null },
+        });
+      });
+    });
+
+    describe("Committee certify results of districts", () => {
+      it("District results roll up to the region and the nation", async () => {
+        await activateUser();
+
+        const committeeActions: CommitteeActions[] = [
+          { CreateDistrict: { name: "Mazowsze", parent_id: [] } },
+          { CreateDistrict: { name: "Warszawa", parent_id: [0n] } },
+          {
+            RegisterNewEntryIdentities: {
+              entry_identities: [entryIdentitySecondUserPrincipal],
+              activation_deadline: [],
+              district_id: [1n],
+            },
+          },
+          {
+            CreateUserPropose: {
+              PresidentialElections: {
+                candidates: ["Jan Kowalski", "Mariusz Broda"],
+                weighted: false,
+                district_id: [],
+              },
+            },
+          },
+        ];
+        await executeCommitteeActions(committeeActions, {
+          onExecuted: async (committeeAction) => {
+            if ("RegisterNewEntryIdentities" in committeeAction) {
+              await activateUser(
+                entryIdentitySecondUser,
+                identitySecondUserPrincipal
+              );
+            }
+          },
+        });
+
+        await getVoteMeBackend(identity).vote_on_propose(
+          { PresidentialElections: 0n },
+          0n
+        );
+        await getVoteMeBackend(identitySecondUser).vote_on_propose(
+          { PresidentialElections: 0n },
+          1n
+        );
+        await expect(
+          getVoteMeBackend(identity).get_presidential_elections_results(0n)
+        ).to.eventually.be.deep.eq({
+          Err: { ResultsNotAvailable: { id: 0n } },
+        });
+
+        await sleep(Number(config.user_proposals_duration / 1_000n / 1_000n));
+
+        await expect(
+          getVoteMeBackend(identity).certify_presidential_elections_results(0n)
+        ).to.eventually.be.deep.eq({ Ok: null });
+        await expect(
+          getVoteMeBackend(identity).get_presidential_elections_results(0n)
+        ).to.eventually.be.deep.eq({
+          Ok: {
+            district_id: [],
+            votes_yes: [1n, 1n],
+            certified_by: [identityPrincipal],
+            certified: true,
+            children: [
+              {
+                district_id: [0n],
+                votes_yes: [0n, 1n],
+                certified_by: [],
+                certified: false,
+                children: [
+                  {
+                    district_id: [1n],
+                    votes_yes: [0n, 1n],
+                    certified_by: [],
+                    certified: false,
+                    children: [],
+                  },
+                ],
+              },
+            ],
+          },
+        });
+      });
+
+      it("Region-scoped elections are open to voters of its districts", async () => {
+        await activateUser();
+
+        const committeeActions: CommitteeActions[] = [
+          { CreateDistrict: { name: "Mazowsze", parent_id: [] } },
+          { CreateDistrict: { name: "Warszawa", parent_id: [0n] } },
+          {
+            RegisterNewEntryIdentities: {
+              entry_identities: [entryIdentitySecondUserPrincipal],
+              activation_deadline: [],
+              district_id: [1n],
+            },
+          },
+          {
+            CreateUserPropose: {
+              PresidentialElections: {
+                candidates: ["Jan Kowalski", "Mariusz Broda"],
+                weighted: false,
+                district_id: [0n],
+              },
+            },
+          },
+        ];
+        await executeCommitteeActions(committeeActions, {
+          onExecuted: async (committeeAction) => {
+            if ("RegisterNewEntryIdentities" in committeeAction) {
+              await activateUser(
+                entryIdentitySecondUser,
+                identitySecondUserPrincipal
+              );
+            }
+          },
+        });
+
+        await expect(
+          getVoteMeBackend(identity).vote_on_propose(
+            { PresidentialElections: 0n },
+            0n
+          )
+        ).to.eventually.be.deep.eq({
+          Err: { NotInDistrict: { id: 0n, district_id: 0n } },
+        });
+        await expect(
+          getVoteMeBackend(identitySecondUser).vote_on_propose(
+            { PresidentialElections: 0n },
+            1n
+          )
+        ).to.eventually.be.deep.eq({ Ok: null });
+        await expect(
+          getVoteMeBackend(identity).get_turnout(
+            { PresidentialElections: null },
+            0n
+          )
+        ).to.eventually.be.deep.eq({
+          Ok: { voters_count: 1n, electorate: 1n },
+        });
+
+        await sleep(Number(config.user_proposals_duration / 1_000n / 1_000n));
+
+        await expect(
+          getVoteMeBackend(identity).get_presidential_elections_results(0n)
+        ).to.eventually.be.deep.eq({
+          Ok: {
+            district_id: [0n],
+            votes_yes: [0n, 1n],
+            certified_by: [],
+            certified: false,
+            children: [
+              {
+                district_id: [1n],
+                votes_yes: [0n, 1n],
+                certified_by: [],
+                certified: false,
+                children: [],
+              },
+            ],
+          },
+        });
+      });
+    });
+
+    describe("Committee 'register entry' identities with activation deadline", () => {
+      it("Unactivated entries expire after the deadline", async () => {
+        const encryptedSeed = await activateUser();
+
+        const activationWindow = 3n * 1_000n * 1_000n * 1_000n;
+        const activationDeadline =
+          BigInt(Date.now()) * 1_000_000n +
+          config.committee_proposals_duration +
+          activationWindow;
+        const proposeId = await getVoteMeBackend(identity)
+          .committee_create_propose({
+            RegisterNewEntryIdentities: {
+              entry_identities: [entryIdentitySecondUserPrincipal],
+              activation_deadline: [activationDeadline],
+              district_id: [],
+            },
+          })
+          .then(unwrap);
+        await getVoteMeBackend(identity)
+          .committee_vote_on_propose(proposeId)
+          .then(unwrap);
+        await sleep(
+          Number(config.committee_proposals_duration / 1_000n / 1_000n)
         );
 
+        const batches = await getVoteMeBackend(identity)
+          .get_registration_batches()
+          .then(unwrap);
+        expect(
+          batches.map(({ registered_at, ...batch }) => batch)
+        ).to.be.deep.eq([
+          {
+            id: 0n,
+            activation_deadline: [activationDeadline],
+            merkle_root: [],
+            district_id: [],
+            pending: 1n,
+            activated: 0n,
+            expired: 0n,
+          },
+        ]);
+
+        await sleep(Number(activationWindow / 1_000n / 1_000n) + 1_000);
+
+        const expiredBatches = await getVoteMeBackend(identity)
+          .get_registration_batches()
+          .then(unwrap);
+        expect(expiredBatches[0]).to.include({
+          pending: 0n,
+          activated: 0n,
+          expired: 1n,
+        });
+        await expect(
+          getVoteMeBackend(entryIdentitySecondUser).activate_user(
+            identitySecondUserPrincipal,
+            encryptedSeed
+          )
+        ).to.eventually.be.deep.eq({
+          Err: {
+            UserNotFound: { principal: entryIdentitySecondUserPrincipal },
+          },
+        });
+      });
+    });
+
+    describe("Committee propose 'promote' user", () => {
+      it("Committee can propose 'promote' user", async () => {
+        await activateUser();
+
         const registerNewEntryIdentities = {
-          RegisterNewEntryIdentities: [entryIdentitySecondUserPrincipal],
+          RegisterNewEntryIdentities: {
+            entry_identities: [entryIdentitySecondUserPrincipal],
+            activation_deadline: [],
+            district_id: [],
+          },
         };
         const registerPropose = await getVoteMeBackend(
           identity
-        ).committee_create_propose(registerNewEntryIdentities);
+        ).committee_create_propose(registerNewEntryIdentities).then(unwrap);
         await expect(
           getVoteMeBackend(identity).committee_vote_on_propose(registerPropose)
-        ).to.be.fulfilled;
+        ).to.eventually.be.deep.eq({ Ok: null });
         await sleep(
           Number(config.committee_proposals_duration / 1_000n / 1_000n)
         );
-        await getVoteMeBackend(entryIdentitySecondUser).activate_user(
-          identitySecondUserPrincipal,
-          encryptedSeed2
+        await activateUser(
+          entryIdentitySecondUser,
+          identitySecondUserPrincipal
         );
 
         const promoteUser = {
@@ -315,11 +1041,11 @@ describe("Vote me", () => {
         };
         const proposeId = await getVoteMeBackend(
           identity
-        ).committee_create_propose(promoteUser);
+        ).committee_create_propose(promoteUser).then(unwrap);
 
         await expect(
           getVoteMeBackend(identity).committee_vote_on_propose(proposeId)
-        ).to.be.fulfilled;
+        ).to.eventually.be.deep.eq({ Ok: null });
 
         await getVoteMeBackend()
           .get_committee_proposals()
@@ -334,7 +1060,8 @@ describe("Vote me", () => {
                 id: 0n,
                 creator: identityPrincipal,
                 action: registerNewEntryIdentities,
-                voters: [identityPrincipal],
+                district_id: [],
+                voters_count: 1n,
                 state: { Accepted: null },
                 votes_yes: 1n,
               },
@@ -342,7 +1069,8 @@ describe("Vote me", () => {
                 id: 1n,
                 creator: identityPrincipal,
                 action: promoteUser,
-                voters: [identityPrincipal],
+                district_id: [],
+                voters_count: 1n,
                 state: { Open: null },
                 votes_yes: 1n,
               },
@@ -365,7 +1093,8 @@ describe("Vote me", () => {
                 id: 0n,
                 creator: identityPrincipal,
                 action: registerNewEntryIdentities,
-                voters: [identityPrincipal],
+                district_id: [],
+                voters_count: 1n,
                 state: { Accepted: null },
                 votes_yes: 1n,
               },
@@ -373,7 +1102,8 @@ describe("Vote me", () => {
                 id: 1n,
                 creator: identityPrincipal,
                 action: promoteUser,
-                voters: [identityPrincipal],
+                district_id: [],
+                voters_count: 1n,
                 state: { Accepted: null },
                 votes_yes: 1n,
               },
@@ -382,39 +1112,153 @@ describe("Vote me", () => {
 
         await expect(
           getVoteMeBackend(identitySecondUser).user_belongs_to_committee()
-        ).to.eventually.be.eq(true);
-      });
-
-      it("User can not vote on 'promote' user propose", async () => {
-        const encryptedSeed = await aes_gcm_encrypt(
-          entryIdentity.getKeyPair().publicKey,
-          user1seed
-        );
-        await getVoteMeBackend(entryIdentity).activate_user(
-          identityPrincipal,
-          encryptedSeed
-        );
+        ).to.eventually.be.deep.eq({ Ok: true });
+
+        const acceptedFilter = {
+          state: [{ Accepted: null }],
+          creator: [identityPrincipal],
+          created_after: [],
+          created_before: [],
+        } as ProposeFilter;
+        await getVoteMeBackend()
+          .list_committee_proposals(acceptedFilter, [], 1n)
+          .then(({ items, next_cursor }) => {
+            expect(items.map(({ id }) => id)).to.be.deep.eq([0n]);
+            expect(next_cursor).to.be.deep.eq([1n]);
+          });
+        await getVoteMeBackend()
+          .list_committee_proposals(acceptedFilter, [1n], 1n)
+          .then(({ items, next_cursor }) => {
+            expect(items.map(({ id }) => id)).to.be.deep.eq([1n]);
+            expect(next_cursor).to.be.deep.eq([]);
+          });
+        // A zero limit still moves the cursor forward
+        await getVoteMeBackend()
+          .list_committee_proposals(acceptedFilter, [], 0n)
+          .then(({ items, next_cursor }) => {
+            expect(items.map(({ id }) => id)).to.be.deep.eq([0n]);
+            expect(next_cursor).to.be.deep.eq([1n]);
+          });
+        await getVoteMeBackend()
+          .list_committee_proposals(
+            { ...acceptedFilter, state: [{ Rejected: null }] },
+            [],
+            10n
+          )
+          .then(({ items }) => expect(items).to.be.empty);
 
-        const encryptedSeed2 = await aes_gcm_encrypt(
-          identitySecondUser.getKeyPair().publicKey,
-          user1seed
+        await getVoteMeBackend()
+          .get_committee_proposal(1n)
+          .then(unwrap)
+          .then(({ action }) => expect(action).to.be.deep.eq(promoteUser));
+        await expect(
+          getVoteMeBackend().get_committee_proposal(2n)
+        ).to.eventually.be.deep.eq({ Err: { ProposeNotFound: { id: 2n } } });
+      });
+
+      it("Committee 'promote' of unknown user is recorded as failed", async () => {
+        await activateUser();
+
+        const promoteUser = {
+          PromoteUser: identitySecondUserPrincipal,
+        };
+        const proposeId = await getVoteMeBackend(
+          identity
+        ).committee_create_propose(promoteUser).then(unwrap);
+        await getVoteMeBackend(identity).committee_vote_on_propose(proposeId);
+
+        await sleep(
+          Number(config.committee_proposals_duration / 1_000n / 1_00n)
         );
 
+        await getVoteMeBackend()
+          .get_committee_proposals()
+          .then(([propose]: CommitteePropose[]) => {
+            expect(propose.state).to.be.deep.eq({
+              Failed: {
+                reason: {
+                  UserNotFound: { principal: identitySecondUserPrincipal },
+                },
+              },
+            });
+          });
+
+        await getVoteMeBackend(identity)
+          .get_events(0n, 100n)
+          .then(unwrap)
+          .then((events: Event[]) => {
+            expect(events.map(({ sequence }) => sequence)).to.be.deep.eq([
+              0n, 1n, 2n, 3n, 4n, 5n,
+            ]);
+            expect(events.map(({ kind }) => Object.keys(kind)[0])).to.be.deep.eq([
+              "ConfigChanged",
+              "UserRegistered",
+              "UserActivated",
+              "ProposeCreated",
+              "ProposeVoted",
+              "ProposeClosed",
+            ]);
+          });
+
+        await expect(
+          getVoteMeBackend(identity).get_events(4n, 1n).then(unwrap)
+        ).to.eventually.have.lengthOf(1);
+
+        const toHex = (bytes: Uint8Array | number[]) =>
+          Buffer.from(bytes).toString("hex");
+        const events = await getVoteMeBackend(identity)
+          .get_events(0n, 100n)
+          .then(unwrap);
+        events.slice(1).forEach((event: Event, index: number) => {
+          expect(toHex(event.previous_hash)).to.be.eq(toHex(events[index].hash));
+        });
+
+        const head = await getVoteMeBackend().get_events_head();
+        expect(head.count).to.be.eq(BigInt(events.length));
+        expect(toHex(head.hash)).to.be.eq(toHex(events[events.length - 1].hash));
+        expect(head.certificate).to.have.lengthOf(1);
+
+        // The log is verified a page at a time, each page continues from the previous one
+        const firstPage = await getVoteMeBackend()
+          .verify_event_log(0n, events[0].previous_hash, 3n)
+          .then(unwrap);
+        expect(firstPage.count).to.be.eq(3n);
+        expect(toHex(firstPage.hash)).to.be.eq(toHex(events[2].hash));
+        const lastPage = await getVoteMeBackend()
+          .verify_event_log(firstPage.count, firstPage.hash, 100n)
+          .then(unwrap);
+        expect(lastPage.count).to.be.eq(head.count);
+        expect(toHex(lastPage.hash)).to.be.eq(toHex(head.hash));
+
+        await expect(
+          getVoteMeBackend().verify_event_log(3n, events[1].hash, 100n)
+        ).to.eventually.be.deep.eq({
+          Err: { EventChainBroken: { sequence: 3n } },
+        });
+      });
+
+      it("User can not vote on 'promote' user propose", async () => {
+        await activateUser();
+
         const registerNewEntryIdentities = {
-          RegisterNewEntryIdentities: [entryIdentitySecondUserPrincipal],
+          RegisterNewEntryIdentities: {
+            entry_identities: [entryIdentitySecondUserPrincipal],
+            activation_deadline: [],
+            district_id: [],
+          },
         };
         const registerPropose = await getVoteMeBackend(
           identity
-        ).committee_create_propose(registerNewEntryIdentities);
+        ).committee_create_propose(registerNewEntryIdentities).then(unwrap);
         await expect(
           getVoteMeBackend(identity).committee_vote_on_propose(registerPropose)
-        ).to.be.fulfilled;
+        ).to.eventually.be.deep.eq({ Ok: null });
         await sleep(
           Number(config.committee_proposals_duration / 1_000n / 1_000n)
         );
-        await getVoteMeBackend(entryIdentitySecondUser).activate_user(
-          identitySecondUserPrincipal,
-          encryptedSeed2
+        await activateUser(
+          entryIdentitySecondUser,
+          identitySecondUserPrincipal
         );
 
         const promoteUser = {
@@ -422,51 +1266,342 @@ describe("Vote me", () => {
         };
         const proposeId = await getVoteMeBackend(
           identity
-        ).committee_create_propose(promoteUser);
+        ).committee_create_propose(promoteUser).then(unwrap);
 
         await expect(
           getVoteMeBackend(identitySecondUser).committee_vote_on_propose(
             proposeId
           )
-        ).to.be.rejectedWith("User do not belongs to committee");
+        ).to.eventually.be.deep.eq({
+          Err: { NotInCommittee: { principal: identitySecondUserPrincipal } },
+        });
 
         await expect(
           getVoteMeBackend(identitySecondUser).user_belongs_to_committee()
-        ).to.eventually.be.eq(false);
+        ).to.eventually.be.deep.eq({ Ok: false });
       });
     });
 
-    describe("Committee propose 'demote' user", () => {
-      it("Committee can propose 'demote' user", async () => {
-        const encryptedSeed = await aes_gcm_encrypt(
-          entryIdentity.getKeyPair().publicKey,
-          user1seed
+    describe("Committee propose 'appoint observer'", () => {
+      it("Observer reads privileged data but can not vote nor propose", async () => {
+        await activateUser();
+
+        const committeeActions: CommitteeActions[] = [
+          {
+            RegisterNewEntryIdentities: {
+              entry_identities: [entryIdentitySecondUserPrincipal],
+              activation_deadline: [],
+              district_id: [],
+            },
+          },
+          { AppointObserver: identitySecondUserPrincipal },
+          {
+            CreateUserPropose: {
+              PresidentialElections: {
+                candidates: ["Jan Kowalski"],
+                weighted: false,
+                district_id: [],
+              },
+            },
+          },
+        ];
+        await executeCommitteeActions(committeeActions, {
+          onExecuted: async (committeeAction) => {
+            if ("RegisterNewEntryIdentities" in committeeAction) {
+              await activateUser(
+                entryIdentitySecondUser,
+                identitySecondUserPrincipal
+              );
+              await expect(
+                getVoteMeBackend(identitySecondUser).get_events(0n, 100n)
+              ).to.eventually.be.deep.eq({
+                Err: {
+                  NotObserver: { principal: identitySecondUserPrincipal },
+                },
+              });
+            }
+          },
+        });
+
+        const events = await getVoteMeBackend(identitySecondUser)
+          .get_events(0n, 100n)
+          .then(unwrap);
+        expect(events.map((event: Event) => event.kind)).to.deep.include({
+          ObserverAppointed: identitySecondUserPrincipal,
+        });
+        await expect(
+          getVoteMeBackend(identitySecondUser).get_committee_proposal_voters(1n)
+        ).to.eventually.be.deep.eq({ Ok: [identityPrincipal] });
+        await expect(
+          getVoteMeBackend(identitySecondUser).get_turnout(
+            { Committee: null },
+            1n
+          )
+        ).to.eventually.be.deep.eq({
+          Ok: { voters_count: 1n, electorate: 1n },
+        });
+        await expect(
+          getVoteMeBackend(identitySecondUser)
+            .get_pending_proposals()
+            .then(unwrap)
+            .then(({ presidential_elections }) =>
+              presidential_elections.map(({ id }) => id)
+            )
+        ).to.eventually.be.deep.eq([0n]);
+
+        await expect(
+          getVoteMeBackend(identitySecondUser).vote_on_propose(
+            { PresidentialElections: 0n },
+            0n
+          )
+        ).to.eventually.be.deep.eq({
+          Err: {
+            ObserverCannotVote: { principal: identitySecondUserPrincipal },
+          },
+        });
+        await expect(
+          getVoteMeBackend(identitySecondUser).committee_create_propose({
+            DemoteUser: identityPrincipal,
+          })
+        ).to.eventually.be.deep.eq({
+          Err: { NotInCommittee: { principal: identitySecondUserPrincipal } },
+        });
+        await expect(
+          getVoteMeBackend(identity).get_turnout(
+            { PresidentialElections: null },
+            0n
+          )
+        ).to.eventually.be.deep.eq({
+          Ok: { voters_count: 0n, electorate: 1n },
+        });
+
+        const appointCommitteeMember = await getVoteMeBackend(identity)
+          .committee_create_propose({ AppointObserver: identityPrincipal })
+          .then(unwrap);
+        await getVoteMeBackend(identity)
+          .committee_vote_on_propose(appointCommitteeMember)
+          .then(unwrap);
+        await sleep(
+          Number(config.committee_proposals_duration / 1_000n / 1_000n)
         );
-        await getVoteMeBackend(entryIdentity).activate_user(
-          identityPrincipal,
-          encryptedSeed
+        await expect(
+          getVoteMeBackend()
+            .get_committee_proposal(appointCommitteeMember)
+            .then(unwrap)
+            .then(({ state }) => state)
+        ).to.eventually.be.deep.eq({
+          Failed: {
+            reason: {
+              CommitteeMemberCannotObserve: { principal: identityPrincipal },
+            },
+          },
+        });
+        await expect(getVoteMeBackend().get_committee_size()).to.eventually.be.eq(
+          1n
         );
+      });
+    });
 
-        const encryptedSeed2 = await aes_gcm_encrypt(
-          identitySecondUser.getKeyPair().publicKey,
-          user1seed
+    describe("Committee announce 'presidential elections'", () => {
+      it("Only candidates with enough nomination signatures are on the ballot", async () => {
+        await activateUser();
+
+        const committeeActions: CommitteeActions[] = [
+          {
+            RegisterNewEntryIdentities: {
+              entry_identities: [entryIdentitySecondUserPrincipal],
+              activation_deadline: [],
+              district_id: [],
+            },
+          },
+          {
+            AnnouncePresidentialElections: {
+              weighted: false,
+              district_id: [],
+            },
+          },
+        ];
+        await executeCommitteeActions(committeeActions);
+        await activateUser(
+          entryIdentitySecondUser,
+          identitySecondUserPrincipal
+        );
+
+        await expect(
+          getVoteMeBackend(identity).declare_candidacy(0n, "Jan Kowalski")
+        ).to.eventually.be.deep.eq({ Ok: null });
+        await expect(
+          getVoteMeBackend(identitySecondUser).declare_candidacy(
+            0n,
+            "Mariusz Broda"
+          )
+        ).to.eventually.be.deep.eq({ Ok: null });
+        await expect(
+          getVoteMeBackend(identitySecondUser).sign_nomination(
+            0n,
+            identitySecondUserPrincipal
+          )
+        ).to.eventually.be.deep.eq({
+          Err: {
+            OwnNominationSigned: {
+              id: 0n,
+              candidate: identitySecondUserPrincipal,
+            },
+          },
+        });
+        await expect(
+          getVoteMeBackend(identitySecondUser).sign_nomination(
+            0n,
+            identityPrincipal
+          )
+        ).to.eventually.be.deep.eq({ Ok: null });
+        await expect(
+          getVoteMeBackend(identitySecondUser).sign_nomination(
+            0n,
+            identityPrincipal
+          )
+        ).to.eventually.be.deep.eq({
+          Err: {
+            NominationAlreadySigned: {
+              id: 0n,
+              candidate: identityPrincipal,
+              principal: identitySecondUserPrincipal,
+            },
+          },
+        });
+
+        await sleep(
+          Number(config.candidate_registration_duration / 1_000n / 1_000n)
+        );
+
+        await expect(
+          getVoteMeBackend(identitySecondUser).sign_nomination(
+            0n,
+            identitySecondUserPrincipal
+          )
+        ).to.eventually.be.deep.eq({
+          Err: { CandidateRegistrationClosed: { id: 0n } },
+        });
+        const [announcement] = await getVoteMeBackend().get_announced_elections();
+        expect(announcement).to.deep.include({
+          registration_open: false,
+          nominated: [identityPrincipal],
+          election_id: [0n],
+        });
+        expect(
+          announcement.candidacies.map(({ name, signatures }) => [
+            name,
+            signatures,
+          ])
+        ).to.be.deep.eq([
+          ["Jan Kowalski", 1n],
+          ["Mariusz Broda", 0n],
+        ]);
+        await expect(
+          getVoteMeBackend()
+            .get_presidential_elections()
+            .then((proposals) =>
+              proposals.map(({ proposal_content, candidates }) => [
+                proposal_content,
+                candidates,
+              ])
+            )
+        ).to.eventually.be.deep.eq([[["Jan Kowalski"], [[identityPrincipal]]]]);
+      });
+
+      it("Runoff keeps the principals of nominated candidates", async () => {
+        const runoffConfig: Config = {
+          ...config,
+          tie_break_policy: { Runoff: null },
+        };
+        deploy(runoffConfig, [entryIdentityPrincipal]);
+
+        await activateUser();
+
+        const committeeActions: CommitteeActions[] = [
+          {
+            RegisterNewEntryIdentities: {
+              entry_identities: [entryIdentitySecondUserPrincipal],
+              activation_deadline: [],
+              district_id: [],
+            },
+          },
+          {
+            AnnouncePresidentialElections: {
+              weighted: false,
+              district_id: [],
+            },
+          },
+        ];
+        await executeCommitteeActions(committeeActions);
+        await activateUser(
+          entryIdentitySecondUser,
+          identitySecondUserPrincipal
         );
 
+        // Candidates back each other, nobody votes and the tie goes to a runoff
+        await getVoteMeBackend(identity)
+          .declare_candidacy(0n, "Jan Kowalski")
+          .then(unwrap);
+        await getVoteMeBackend(identitySecondUser)
+          .declare_candidacy(0n, "Mariusz Broda")
+          .then(unwrap);
+        await getVoteMeBackend(identity)
+          .sign_nomination(0n, identitySecondUserPrincipal)
+          .then(unwrap);
+        await getVoteMeBackend(identitySecondUser)
+          .sign_nomination(0n, identityPrincipal)
+          .then(unwrap);
+        await sleep(
+          Number(runoffConfig.candidate_registration_duration / 1_000n / 1_000n)
+        );
+        await sleep(
+          Number(runoffConfig.committee_proposals_duration / 1_000n / 1_000n)
+        );
+
+        await expect(
+          getVoteMeBackend()
+            .get_presidential_elections()
+            .then((proposals) =>
+              proposals.map(({ round, proposal_content, candidates }) => ({
+                round,
+                proposal_content,
+                candidates,
+              }))
+            )
+        ).to.eventually.be.deep.eq(
+          [1, 2].map((round) => ({
+            round,
+            proposal_content: ["Jan Kowalski", "Mariusz Broda"],
+            candidates: [[identityPrincipal], [identitySecondUserPrincipal]],
+          }))
+        );
+      });
+    });
+
+    describe("Committee propose 'demote' user", () => {
+      it("Committee can propose 'demote' user", async () => {
+        await activateUser();
+
         const registerNewEntryIdentities = {
-          RegisterNewEntryIdentities: [entryIdentitySecondUserPrincipal],
+          RegisterNewEntryIdentities: {
+            entry_identities: [entryIdentitySecondUserPrincipal],
+            activation_deadline: [],
+            district_id: [],
+          },
         };
         const registerPropose = await getVoteMeBackend(
           identity
-        ).committee_create_propose(registerNewEntryIdentities);
+        ).committee_create_propose(registerNewEntryIdentities).then(unwrap);
         await expect(
           getVoteMeBackend(identity).committee_vote_on_propose(registerPropose)
-        ).to.be.fulfilled;
+        ).to.eventually.be.deep.eq({ Ok: null });
         await sleep(
           Number(config.committee_proposals_duration / 1_000n / 1_000n)
         );
-        await getVoteMeBackend(entryIdentitySecondUser).activate_user(
-          identitySecondUserPrincipal,
-          encryptedSeed2
+        await activateUser(
+          entryIdentitySecondUser,
+          identitySecondUserPrincipal
         );
 
         const demoteUser = {
@@ -474,11 +1609,11 @@ describe("Vote me", () => {
         };
         const proposeId = await getVoteMeBackend(
           identity
-        ).committee_create_propose(demoteUser);
+        ).committee_create_propose(demoteUser).then(unwrap);
 
         await expect(
           getVoteMeBackend(identity).committee_vote_on_propose(proposeId)
-        ).to.be.fulfilled;
+        ).to.eventually.be.deep.eq({ Ok: null });
 
         await getVoteMeBackend()
           .get_committee_proposals()
@@ -493,7 +1628,8 @@ describe("Vote me", () => {
                 id: 0n,
                 creator: identityPrincipal,
                 action: registerNewEntryIdentities,
-                voters: [identityPrincipal],
+                district_id: [],
+                voters_count: 1n,
                 state: { Accepted: null },
                 votes_yes: 1n,
               },
@@ -501,7 +1637,8 @@ describe("Vote me", () => {
                 id: 1n,
                 creator: identityPrincipal,
                 action: demoteUser,
-                voters: [identityPrincipal],
+                district_id: [],
+                voters_count: 1n,
                 state: { Open: null },
                 votes_yes: 1n,
               },
@@ -524,7 +1661,8 @@ describe("Vote me", () => {
                 id: 0n,
                 creator: identityPrincipal,
                 action: registerNewEntryIdentities,
-                voters: [identityPrincipal],
+                district_id: [],
+                voters_count: 1n,
                 state: { Accepted: null },
                 votes_yes: 1n,
               },
@@ -532,7 +1670,8 @@ describe("Vote me", () => {
                 id: 1n,
                 creator: identityPrincipal,
                 action: demoteUser,
-                voters: [identityPrincipal],
+                district_id: [],
+                voters_count: 1n,
                 state: { Accepted: null },
                 votes_yes: 1n,
               },
@@ -541,39 +1680,31 @@ describe("Vote me", () => {
 
         await expect(
           getVoteMeBackend(identitySecondUser).user_belongs_to_committee()
-        ).to.eventually.be.eq(false);
+        ).to.eventually.be.deep.eq({ Ok: false });
       });
 
       it("User can not vote on 'demote' user propose", async () => {
-        const encryptedSeed = await aes_gcm_encrypt(
-          entryIdentity.getKeyPair().publicKey,
-          user1seed
-        );
-        await getVoteMeBackend(entryIdentity).activate_user(
-          identityPrincipal,
-          encryptedSeed
-        );
-
-        const encryptedSeed2 = await aes_gcm_encrypt(
-          identitySecondUser.getKeyPair().publicKey,
-          user1seed
-        );
+        await activateUser();
 
         const registerNewEntryIdentities = {
-          RegisterNewEntryIdentities: [entryIdentitySecondUserPrincipal],
+          RegisterNewEntryIdentities: {
+            entry_identities: [entryIdentitySecondUserPrincipal],
+            activation_deadline: [],
+            district_id: [],
+          },
         };
         const registerPropose = await getVoteMeBackend(
           identity
-        ).committee_create_propose(registerNewEntryIdentities);
+        ).committee_create_propose(registerNewEntryIdentities).then(unwrap);
         await expect(
           getVoteMeBackend(identity).committee_vote_on_propose(registerPropose)
-        ).to.be.fulfilled;
+        ).to.eventually.be.deep.eq({ Ok: null });
         await sleep(
           Number(config.committee_proposals_duration / 1_000n / 1_000n)
         );
-        await getVoteMeBackend(entryIdentitySecondUser).activate_user(
-          identitySecondUserPrincipal,
-          encryptedSeed2
+        await activateUser(
+          entryIdentitySecondUser,
+          identitySecondUserPrincipal
         );
 
         const demoteUser = {
@@ -581,67 +1712,312 @@ describe("Vote me", () => {
         };
         const proposeId = await getVoteMeBackend(
           identity
-        ).committee_create_propose(demoteUser);
+        ).committee_create_propose(demoteUser).then(unwrap);
 
         await expect(
           getVoteMeBackend(identitySecondUser).committee_vote_on_propose(
             proposeId
           )
-        ).to.be.rejectedWith("User do not belongs to committee");
+        ).to.eventually.be.deep.eq({
+          Err: { NotInCommittee: { principal: identitySecondUserPrincipal } },
+        });
+        await expect(
+          getVoteMeBackend(identitySecondUser).user_belongs_to_committee()
+        ).to.eventually.be.deep.eq({ Ok: false });
+      });
+    });
+
+    describe("Committee propose 'reset identity' of user", () => {
+      it("Committee can reset identity and user activates a new one after cooldown", async () => {
+        await activateUser();
+
+        const encryptedSeed2 = await aes_gcm_encrypt(
+          identitySecondUser.getKeyPair().publicKey,
+          user1seed
+        );
+
+        const registerPropose = await getVoteMeBackend(identity)
+          .committee_create_propose({
+            RegisterNewEntryIdentities: {
+              entry_identities: [entryIdentitySecondUserPrincipal],
+              activation_deadline: [],
+              district_id: [],
+            },
+          })
+          .then(unwrap);
+        await getVoteMeBackend(identity)
+          .committee_vote_on_propose(registerPropose)
+          .then(unwrap);
+        await sleep(
+          Number(config.committee_proposals_duration / 1_000n / 1_000n)
+        );
+        await activateUser(
+          entryIdentitySecondUser,
+          identitySecondUserPrincipal
+        );
+
+        const proposeId = await getVoteMeBackend(identity)
+          .committee_create_propose({
+            ResetUserIdentity: entryIdentitySecondUserPrincipal,
+          })
+          .then(unwrap);
+        await getVoteMeBackend(identity)
+          .committee_vote_on_propose(proposeId)
+          .then(unwrap);
+        await sleep(
+          Number(config.committee_proposals_duration / 1_000n / 1_000n)
+        );
+
         await expect(
           getVoteMeBackend(identitySecondUser).user_belongs_to_committee()
-        ).to.eventually.be.eq(false);
+        ).to.eventually.be.deep.eq({
+          Err: { UserNotFound: { principal: identitySecondUserPrincipal } },
+        });
+
+        const events = await getVoteMeBackend(identity)
+          .get_events(0n, 100n)
+          .then(unwrap);
+        expect(events.map((event: Event) => event.kind)).to.deep.include({
+          UserIdentityReset: {
+            entry_identity: entryIdentitySecondUserPrincipal,
+            identity: identitySecondUserPrincipal,
+          },
+        });
+
+        const activation = await getVoteMeBackend(
+          entryIdentitySecondUser
+        ).activate_user(identitySecondUserPrincipal, encryptedSeed2);
+        expect(activation).to.have.nested.property(
+          "Err.IdentityResetCooldown.entry_identity"
+        );
+
+        await sleep(Number(config.identity_reset_cooldown / 1_000n / 1_000n));
+
+        await expect(
+          getVoteMeBackend(entryIdentitySecondUser).activate_user(
+            identitySecondUserPrincipal,
+            encryptedSeed2
+          )
+        ).to.eventually.be.deep.eq({ Ok: null });
+      });
+
+      it("User can not vote again after an identity reset", async () => {
+        // Proposals outlive the reset so the election is still open once the user is back
+        const duration = 10n * 1_000n * 1_000n * 1_000n;
+        const halfDuration = Number(duration / 2n / 1_000n / 1_000n);
+        deploy(
+          {
+            ...config,
+            committee_proposals_duration: duration,
+            identity_reset_cooldown: 0n,
+          },
+          [entryIdentityPrincipal]
+        );
+
+        await activateUser();
+
+        const registerPropose = await getVoteMeBackend(identity)
+          .committee_create_propose({
+            RegisterNewEntryIdentities: {
+              entry_identities: [entryIdentitySecondUserPrincipal],
+              activation_deadline: [],
+              district_id: [],
+            },
+          })
+          .then(unwrap);
+        await getVoteMeBackend(identity)
+          .committee_vote_on_propose(registerPropose)
+          .then(unwrap);
+        await sleep(Number(duration / 1_000n / 1_000n));
+        await activateUser(
+          entryIdentitySecondUser,
+          identitySecondUserPrincipal
+        );
+
+        const electionPropose = await getVoteMeBackend(identity)
+          .committee_create_propose({
+            CreateUserPropose: {
+              PresidentialElections: {
+                candidates: ["Jan Kowalski", "Mariusz Broda"],
+                weighted: false,
+                district_id: [],
+              },
+            },
+          })
+          .then(unwrap);
+        await getVoteMeBackend(identity)
+          .committee_vote_on_propose(electionPropose)
+          .then(unwrap);
+        await sleep(halfDuration);
+
+        const resetPropose = await getVoteMeBackend(identity)
+          .committee_create_propose({
+            ResetUserIdentity: entryIdentitySecondUserPrincipal,
+          })
+          .then(unwrap);
+        await getVoteMeBackend(identity)
+          .committee_vote_on_propose(resetPropose)
+          .then(unwrap);
+        await sleep(halfDuration + 1_000);
+
+        await getVoteMeBackend(identitySecondUser)
+          .vote_on_propose({ PresidentialElections: 0n }, 0n)
+          .then(unwrap);
+        await sleep(halfDuration);
+
+        await activateUser(
+          entryIdentitySecondUser,
+          identitySecondUserPrincipal
+        );
+
+        await expect(
+          getVoteMeBackend(identitySecondUser).vote_on_propose(
+            { PresidentialElections: 0n },
+            1n
+          )
+        ).to.eventually.be.deep.eq({
+          Err: {
+            UserAlreadyVoted: {
+              id: 0n,
+              principal: identitySecondUserPrincipal,
+            },
+          },
+        });
+      });
+    });
+
+    describe("Committee propose 'suspend' user", () => {
+      it("Suspended user is excluded until reinstated", async () => {
+        await activateUser();
+
+        const registerPropose = await getVoteMeBackend(identity)
+          .committee_create_propose({
+            RegisterNewEntryIdentities: {
+              entry_identities: [entryIdentitySecondUserPrincipal],
+              activation_deadline: [],
+              district_id: [],
+            },
+          })
+          .then(unwrap);
+        await getVoteMeBackend(identity)
+          .committee_vote_on_propose(registerPropose)
+          .then(unwrap);
+        await sleep(
+          Number(config.committee_proposals_duration / 1_000n / 1_000n)
+        );
+        await activateUser(
+          entryIdentitySecondUser,
+          identitySecondUserPrincipal
+        );
+
+        const suspendUser = {
+          SuspendUser: [entryIdentitySecondUserPrincipal, "Relocated"],
+        };
+        const proposeId = await getVoteMeBackend(identity)
+          .committee_create_propose(suspendUser)
+          .then(unwrap);
+        await getVoteMeBackend(identity)
+          .committee_vote_on_propose(proposeId)
+          .then(unwrap);
+        await sleep(
+          Number(config.committee_proposals_duration / 1_000n / 1_000n)
+        );
+
+        const status = { Suspended: { reason: "Relocated" } };
+        await expect(
+          getVoteMeBackend(identitySecondUser).approve_identity_change(
+            identityPrincipal
+          )
+        ).to.eventually.be.deep.eq({
+          Err: { IdentityAlreadyBound: { identity: identityPrincipal } },
+        });
+        await expect(
+          getVoteMeBackend(identitySecondUser).approve_identity_change(
+            entryIdentitySecondUserPrincipal
+          )
+        ).to.eventually.be.deep.eq({
+          Err: {
+            UserNotActive: {
+              entry_identity: entryIdentitySecondUserPrincipal,
+              status,
+            },
+          },
+        });
+
+        const events = await getVoteMeBackend(identity)
+          .get_events(0n, 100n)
+          .then(unwrap);
+        expect(events.map((event: Event) => event.kind)).to.deep.include({
+          UserStatusChanged: {
+            entry_identity: entryIdentitySecondUserPrincipal,
+            status,
+          },
+        });
+
+        const reinstateId = await getVoteMeBackend(identity)
+          .committee_create_propose({
+            ReinstateUser: entryIdentitySecondUserPrincipal,
+          })
+          .then(unwrap);
+        await getVoteMeBackend(identity)
+          .committee_vote_on_propose(reinstateId)
+          .then(unwrap);
+        await sleep(
+          Number(config.committee_proposals_duration / 1_000n / 1_000n)
+        );
+
+        await expect(
+          getVoteMeBackend(identitySecondUser).approve_identity_change(
+            entryIdentitySecondUserPrincipal
+          )
+        ).to.eventually.be.deep.eq({ Ok: null });
       });
     });
 
     describe("Committee create user proposal", () => {
       describe("CreateUserPropose", () => {
         it("Committee can create 'presidential elections'", async () => {
-          const encryptedSeed = await aes_gcm_encrypt(
-            entryIdentity.getKeyPair().publicKey,
-            user1seed
-          );
-          await getVoteMeBackend(entryIdentity).activate_user(
-            identityPrincipal,
-            encryptedSeed
-          );
-
-          const encryptedSeed2 = await aes_gcm_encrypt(
-            identitySecondUser.getKeyPair().publicKey,
-            user1seed
-          );
+          await activateUser();
 
           const registerNewEntryIdentities = {
-            RegisterNewEntryIdentities: [entryIdentitySecondUserPrincipal],
+            RegisterNewEntryIdentities: {
+              entry_identities: [entryIdentitySecondUserPrincipal],
+              activation_deadline: [],
+              district_id: [],
+            },
           };
           const registerPropose = await getVoteMeBackend(
             identity
-          ).committee_create_propose(registerNewEntryIdentities);
+          ).committee_create_propose(registerNewEntryIdentities).then(unwrap);
           await expect(
             getVoteMeBackend(identity).committee_vote_on_propose(
               registerPropose
             )
-          ).to.be.fulfilled;
+          ).to.eventually.be.deep.eq({ Ok: null });
           await sleep(
             Number(config.committee_proposals_duration / 1_000n / 1_000n)
           );
-          await getVoteMeBackend(entryIdentitySecondUser).activate_user(
-            identitySecondUserPrincipal,
-            encryptedSeed2
+          await activateUser(
+            entryIdentitySecondUser,
+            identitySecondUserPrincipal
           );
 
           const createUserPropose: CommitteeActions = {
             CreateUserPropose: {
-              PresidentialElections: ["Jan Kowalski"],
+              PresidentialElections: {
+                candidates: ["Jan Kowalski"],
+                weighted: false,
+                district_id: [],
+              },
             },
           };
           const proposeId = await getVoteMeBackend(
             identity
-          ).committee_create_propose(createUserPropose);
+          ).committee_create_propose(createUserPropose).then(unwrap);
 
           await expect(
             getVoteMeBackend(identity).committee_vote_on_propose(proposeId)
-          ).to.be.fulfilled;
+          ).to.eventually.be.deep.eq({ Ok: null });
 
           await getVoteMeBackend()
             .get_committee_proposals()
@@ -656,7 +2032,8 @@ describe("Vote me", () => {
                   id: 0n,
                   creator: identityPrincipal,
                   action: registerNewEntryIdentities,
-                  voters: [identityPrincipal],
+                  district_id: [],
+                  voters_count: 1n,
                   state: { Accepted: null },
                   votes_yes: 1n,
                 },
@@ -664,7 +2041,8 @@ describe("Vote me", () => {
                   id: 1n,
                   creator: identityPrincipal,
                   action: createUserPropose,
-                  voters: [identityPrincipal],
+                  district_id: [],
+                  voters_count: 1n,
                   state: { Open: null },
                   votes_yes: 1n,
                 },
@@ -687,7 +2065,8 @@ describe("Vote me", () => {
                   id: 0n,
                   creator: identityPrincipal,
                   action: registerNewEntryIdentities,
-                  voters: [identityPrincipal],
+                  district_id: [],
+                  voters_count: 1n,
                   state: { Accepted: null },
                   votes_yes: 1n,
                 },
@@ -695,7 +2074,8 @@ describe("Vote me", () => {
                   id: 1n,
                   creator: identityPrincipal,
                   action: createUserPropose,
-                  voters: [identityPrincipal],
+                  district_id: [],
+                  voters_count: 1n,
                   state: { Accepted: null },
                   votes_yes: 1n,
                 },
@@ -714,10 +2094,15 @@ describe("Vote me", () => {
                 {
                   id: 0n,
                   creator: identityPrincipal,
-                  voters: [],
+                  tie_break: [],
+                  winner: [],
+                  voters_count: 0n,
+                  parent_id: [],
                   state: { Open: null },
+                  weighted: false,
 
                   votes_yes: [],
+                  round: 1,
                   vote_content: createUserPropose.CreateUserPropose,
                 },
               ]);
@@ -725,51 +2110,47 @@ describe("Vote me", () => {
         });
 
         it("User can vote on 'presidential elections' and winner is over a threshold", async () => {
-          const encryptedSeed = await aes_gcm_encrypt(
-            entryIdentity.getKeyPair().publicKey,
-            user1seed
-          );
-          await getVoteMeBackend(entryIdentity).activate_user(
-            identityPrincipal,
-            encryptedSeed
-          );
-
-          const encryptedSeed2 = await aes_gcm_encrypt(
-            identitySecondUser.getKeyPair().publicKey,
-            user1seed
-          );
+          await activateUser();
 
           const registerNewEntryIdentities = {
-            RegisterNewEntryIdentities: [entryIdentitySecondUserPrincipal],
+            RegisterNewEntryIdentities: {
+              entry_identities: [entryIdentitySecondUserPrincipal],
+              activation_deadline: [],
+              district_id: [],
+            },
           };
           const registerPropose = await getVoteMeBackend(
             identity
-          ).committee_create_propose(registerNewEntryIdentities);
+          ).committee_create_propose(registerNewEntryIdentities).then(unwrap);
           await expect(
             getVoteMeBackend(identity).committee_vote_on_propose(
               registerPropose
             )
-          ).to.be.fulfilled;
+          ).to.eventually.be.deep.eq({ Ok: null });
           await sleep(
             Number(config.committee_proposals_duration / 1_000n / 1_000n)
           );
-          await getVoteMeBackend(entryIdentitySecondUser).activate_user(
-            identitySecondUserPrincipal,
-            encryptedSeed2
+          await activateUser(
+            entryIdentitySecondUser,
+            identitySecondUserPrincipal
           );
 
           const createUserPropose: CommitteeActions = {
             CreateUserPropose: {
-              PresidentialElections: ["Jan Kowalski"],
+              PresidentialElections: {
+                candidates: ["Jan Kowalski"],
+                weighted: false,
+                district_id: [],
+              },
             },
           };
           const proposeId = await getVoteMeBackend(
             identity
-          ).committee_create_propose(createUserPropose);
+          ).committee_create_propose(createUserPropose).then(unwrap);
 
           await expect(
             getVoteMeBackend(identity).committee_vote_on_propose(proposeId)
-          ).to.be.fulfilled;
+          ).to.eventually.be.deep.eq({ Ok: null });
 
           await getVoteMeBackend()
             .get_committee_proposals()
@@ -784,7 +2165,8 @@ describe("Vote me", () => {
                   id: 0n,
                   creator: identityPrincipal,
                   action: registerNewEntryIdentities,
-                  voters: [identityPrincipal],
+                  district_id: [],
+                  voters_count: 1n,
                   state: { Accepted: null },
                   votes_yes: 1n,
                 },
@@ -792,7 +2174,8 @@ describe("Vote me", () => {
                   id: 1n,
                   creator: identityPrincipal,
                   action: createUserPropose,
-                  voters: [identityPrincipal],
+                  district_id: [],
+                  voters_count: 1n,
                   state: { Open: null },
                   votes_yes: 1n,
                 },
@@ -815,7 +2198,8 @@ describe("Vote me", () => {
                   id: 0n,
                   creator: identityPrincipal,
                   action: registerNewEntryIdentities,
-                  voters: [identityPrincipal],
+                  district_id: [],
+                  voters_count: 1n,
                   state: { Accepted: null },
                   votes_yes: 1n,
                 },
@@ -823,7 +2207,8 @@ describe("Vote me", () => {
                   id: 1n,
                   creator: identityPrincipal,
                   action: createUserPropose,
-                  voters: [identityPrincipal],
+                  district_id: [],
+                  voters_count: 1n,
                   state: { Accepted: null },
                   votes_yes: 1n,
                 },
@@ -843,13 +2228,21 @@ describe("Vote me", () => {
                   {
                     id: 0n,
                     creator: identityPrincipal,
-                    voters: [],
+                    tie_break: [],
+                    winner: [],
+                    parent_id: [],
                     state: { Open: null },
+                    weighted: false,
+                    delegated_votes: { "0": "0" },
+                    district_id: [],
                     votes_yes: { "0": "0" },
+                    district_votes: [],
+                    round: 1,
+                    voters_count: 0n,
                     proposal_content:
                       createUserPropose.CreateUserPropose[
                         "PresidentialElections"
-                      ],
+                      ].candidates,
                   },
                 ])
               );
@@ -862,6 +2255,19 @@ describe("Vote me", () => {
             0n
           );
 
+          await expect(
+            getVoteMeBackend(identitySecondUser).has_voted(
+              { PresidentialElections: null },
+              0n
+            )
+          ).to.eventually.be.deep.eq({ Ok: true });
+          await expect(
+            getVoteMeBackend(identity).has_voted(
+              { PresidentialElections: null },
+              0n
+            )
+          ).to.eventually.be.deep.eq({ Ok: false });
+
           await sleep(Number(config.user_proposals_duration / 1_000n / 1_000n));
 
           await getVoteMeBackend(identitySecondUser)
@@ -877,65 +2283,69 @@ describe("Vote me", () => {
                   {
                     id: 0n,
                     creator: identityPrincipal,
-                    voters: [identitySecondUserPrincipal],
+                    tie_break: [],
+                    winner: [0n],
+                    parent_id: [],
                     state: { Accepted: null },
+                    weighted: false,
+                    delegated_votes: { "0": 0n },
+                    district_id: [],
                     votes_yes: { "0": 1n },
+                    district_votes: [],
+                    round: 1,
+                    voters_count: 1n,
                     proposal_content:
                       createUserPropose.CreateUserPropose[
                         "PresidentialElections"
-                      ],
+                      ].candidates,
                   },
                 ])
               );
             });
         });
 
-        it("User can vote on 'presidential elections'. When there is no winner it is Unresolved (< 2)", async () => {
-          const encryptedSeed = await aes_gcm_encrypt(
-            entryIdentity.getKeyPair().publicKey,
-            user1seed
-          );
-          await getVoteMeBackend(entryIdentity).activate_user(
-            identityPrincipal,
-            encryptedSeed
-          );
-
-          const encryptedSeed2 = await aes_gcm_encrypt(
-            identitySecondUser.getKeyPair().publicKey,
-            user1seed
-          );
+        it("User can vote on 'presidential elections'. When there is no winner it is Unresolved until the committee resolves the tie (< 2)", async () => {
+          await activateUser();
 
           const registerNewEntryIdentities = {
-            RegisterNewEntryIdentities: [entryIdentitySecondUserPrincipal],
+            RegisterNewEntryIdentities: {
+              entry_identities: [entryIdentitySecondUserPrincipal],
+              activation_deadline: [],
+              district_id: [],
+            },
           };
           const registerPropose = await getVoteMeBackend(
             identity
-          ).committee_create_propose(registerNewEntryIdentities);
+          ).committee_create_propose(registerNewEntryIdentities).then(unwrap);
           await expect(
             getVoteMeBackend(identity).committee_vote_on_propose(
               registerPropose
             )
-          ).to.be.fulfilled;
+          ).to.eventually.be.deep.eq({ Ok: null });
           await sleep(
             Number(config.committee_proposals_duration / 1_000n / 1_000n)
           );
-          await getVoteMeBackend(entryIdentitySecondUser).activate_user(
-            identitySecondUserPrincipal,
-            encryptedSeed2
+          await activateUser(
+            entryIdentitySecondUser,
+            identitySecondUserPrincipal
           );
 
           const createUserPropose: CommitteeActions = {
             CreateUserPropose: {
-              PresidentialElections: ["Jan Kowalski", "Mariusz Broda"],
+              PresidentialElections: {
+                candidates: ["Jan Kowalski", "Mariusz Broda"],
+                weighted: false,
+                district_id: [],
+              },
             },
           };
           const proposeId = await getVoteMeBackend(
             identity
-          ).committee_create_propose(createUserPropose);
+          ).committee_create_propose(createUserPropose).then(unwrap);
 
           await expect(
             getVoteMeBackend(identity).committee_vote_on_propose(proposeId)
-          ).to.be.fulfilled;
+          ).to.eventually.be.deep.eq({ Ok: null });
 
           await getVoteMeBackend()
             .get_committee_proposals()
@@ -950,7 +2360,8 @@ describe("Vote me", () => {
                   id: 0n,
                   creator: identityPrincipal,
                   action: registerNewEntryIdentities,
-                  voters: [identityPrincipal],
+                  district_id: [],
+                  voters_count: 1n,
                   state: { Accepted: null },
                   votes_yes: 1n,
                 },
@@ -958,7 +2369,8 @@ describe("Vote me", () => {
                   id: 1n,
                   creator: identityPrincipal,
                   action: createUserPropose,
-                  voters: [identityPrincipal],
+                  district_id: [],
+                  voters_count: 1n,
                   state: { Open: null },
                   votes_yes: 1n,
                 },
@@ -981,7 +2393,8 @@ describe("Vote me", () => {
                   id: 0n,
                   creator: identityPrincipal,
                   action: registerNewEntryIdentities,
-                  voters: [identityPrincipal],
+                  district_id: [],
+                  voters_count: 1n,
                   state: { Accepted: null },
                   votes_yes: 1n,
                 },
@@ -989,7 +2402,8 @@ describe("Vote me", () => {
                   id: 1n,
                   creator: identityPrincipal,
                   action: createUserPropose,
-                  voters: [identityPrincipal],
+                  district_id: [],
+                  voters_count: 1n,
                   state: { Accepted: null },
                   votes_yes: 1n,
                 },
@@ -1009,13 +2423,21 @@ describe("Vote me", () => {
                   {
                     id: 0n,
                     creator: identityPrincipal,
-                    voters: [],
+                    tie_break: [],
+                    winner: [],
+                    parent_id: [],
                     state: { Open: null },
+                    weighted: false,
+                    delegated_votes: { "0": 0n, "1": 0n },
+                    district_id: [],
                     votes_yes: { "0": 0n, "1": 0n },
+                    district_votes: [],
+                    round: 1,
+                    voters_count: 0n,
                     proposal_content:
                       createUserPropose.CreateUserPropose[
                         "PresidentialElections"
-                      ],
+                      ].candidates,
                   },
                 ])
               );
@@ -1036,69 +2458,107 @@ describe("Vote me", () => {
                   {
                     id: 0n,
                     creator: identityPrincipal,
-                    voters: [],
+                    tie_break: [
+                      {
+                        seed: [],
+                        tied_candidates: [0n, 1n],
+                        stage: { Winner: null },
+                        chosen: [],
+                        policy: { CommitteeDecision: null },
+                      },
+                    ],
+                    winner: [],
+                    parent_id: [],
                     state: { Unresolved: null },
+                    weighted: false,
+                    delegated_votes: { "0": 0n, "1": 0n },
+                    district_id: [],
                     votes_yes: { "0": 0n, "1": 0n },
+                    district_votes: [],
+                    round: 1,
+                    voters_count: 0n,
                     proposal_content:
                       createUserPropose.CreateUserPropose[
                         "PresidentialElections"
-                      ],
+                      ].candidates,
                   },
                 ])
               );
             });
-        });
 
-        it("User can vote on 'presidential elections'. When there is no winner, create new vote (> 2)", async () => {
-          const encryptedSeed = await aes_gcm_encrypt(
-            entryIdentity.getKeyPair().publicKey,
-            user1seed
+          const resolveTie: CommitteeActions = {
+            ResolvePresidentialElectionsTie: {
+              propose_id: 0n,
+              candidates: [1n],
+            },
+          };
+          const resolveTieProposeId = await getVoteMeBackend(
+            identity
+          ).committee_create_propose(resolveTie).then(unwrap);
+          await getVoteMeBackend(identity).committee_vote_on_propose(
+            resolveTieProposeId
           );
-          await getVoteMeBackend(entryIdentity).activate_user(
-            identityPrincipal,
-            encryptedSeed
+          await sleep(
+            Number(config.committee_proposals_duration / 1_000n / 1_000n)
           );
 
-          const encryptedSeed2 = await aes_gcm_encrypt(
-            identitySecondUser.getKeyPair().publicKey,
-            user1seed
-          );
+          await getVoteMeBackend(identitySecondUser)
+            .get_presidential_elections()
+            .then((_proposals: PresidentialElectionsProposeCandidType[]) => {
+              const [propose] = _proposals;
+
+              expect(propose.state).to.be.deep.eq({ Accepted: null });
+              expect(propose.winner).to.be.deep.eq([1n]);
+              expect(propose.tie_break[0]?.chosen).to.be.deep.eq([1n]);
+            });
+        });
+
+        it("User can vote on 'presidential elections'. When there is no winner, create new vote among all tied candidates (> 2)", async () => {
+          await activateUser();
 
           const registerNewEntryIdentities = {
-            RegisterNewEntryIdentities: [entryIdentitySecondUserPrincipal],
+            RegisterNewEntryIdentities: {
+              entry_identities: [entryIdentitySecondUserPrincipal],
+              activation_deadline: [],
+              district_id: [],
+            },
           };
           const registerPropose = await getVoteMeBackend(
             identity
-          ).committee_create_propose(registerNewEntryIdentities);
+          ).committee_create_propose(registerNewEntryIdentities).then(unwrap);
           await expect(
             getVoteMeBackend(identity).committee_vote_on_propose(
               registerPropose
             )
-          ).to.be.fulfilled;
+          ).to.eventually.be.deep.eq({ Ok: null });
           await sleep(
             Number(config.committee_proposals_duration / 1_000n / 1_000n)
           );
-          await getVoteMeBackend(entryIdentitySecondUser).activate_user(
-            identitySecondUserPrincipal,
-            encryptedSeed2
+          await activateUser(
+            entryIdentitySecondUser,
+            identitySecondUserPrincipal
           );
 
           const createUserPropose: CommitteeActions = {
             CreateUserPropose: {
-              PresidentialElections: [
-                "Jan Kowalski",
-                "Mariusz Broda",
-                "Andrzej Kłoda",
-              ],
+              PresidentialElections: {
+                candidates: [
+                  "Jan Kowalski",
+                  "Mariusz Broda",
+                  "Andrzej Kłoda",
+                ],
+                weighted: false,
+                district_id: [],
+              },
             },
           };
           const proposeId = await getVoteMeBackend(
             identity
-          ).committee_create_propose(createUserPropose);
+          ).committee_create_propose(createUserPropose).then(unwrap);
 
           await expect(
             getVoteMeBackend(identity).committee_vote_on_propose(proposeId)
-          ).to.be.fulfilled;
+          ).to.eventually.be.deep.eq({ Ok: null });
 
           await getVoteMeBackend()
             .get_committee_proposals()
@@ -1113,7 +2573,8 @@ describe("Vote me", () => {
                   id: 0n,
                   creator: identityPrincipal,
                   action: registerNewEntryIdentities,
-                  voters: [identityPrincipal],
+                  district_id: [],
+                  voters_count: 1n,
                   state: { Accepted: null },
                   votes_yes: 1n,
                 },
@@ -1121,7 +2582,8 @@ describe("Vote me", () => {
                   id: 1n,
                   creator: identityPrincipal,
                   action: createUserPropose,
-                  voters: [identityPrincipal],
+                  district_id: [],
+                  voters_count: 1n,
                   state: { Open: null },
                   votes_yes: 1n,
                 },
@@ -1144,7 +2606,8 @@ describe("Vote me", () => {
                   id: 0n,
                   creator: identityPrincipal,
                   action: registerNewEntryIdentities,
-                  voters: [identityPrincipal],
+                  district_id: [],
+                  voters_count: 1n,
                   state: { Accepted: null },
                   votes_yes: 1n,
                 },
@@ -1152,7 +2615,8 @@ describe("Vote me", () => {
                   id: 1n,
                   creator: identityPrincipal,
                   action: createUserPropose,
-                  voters: [identityPrincipal],
+                  district_id: [],
+                  voters_count: 1n,
                   state: { Accepted: null },
                   votes_yes: 1n,
                 },
@@ -1173,13 +2637,21 @@ describe("Vote me", () => {
                     {
                       id: 0n,
                       creator: identityPrincipal,
-                      voters: [],
+                      tie_break: [],
+                      winner: [],
+                      voters_count: 0n,
+                      parent_id: [],
                       state: { Open: null },
+                      weighted: false,
+                      delegated_votes: { "0": 0n, "1": 0n, "2": 0n },
+                      district_id: [],
                       votes_yes: { "0": 0n, "1": 0n, "2": 0n },
+                      district_votes: [],
+                      round: 1,
                       proposal_content:
                         createUserPropose.CreateUserPropose[
                           "PresidentialElections"
-                        ],
+                        ].candidates,
                     },
                   ],
                   null,
@@ -1204,28 +2676,48 @@ describe("Vote me", () => {
                     {
                       id: 0n,
                       creator: identityPrincipal,
-                      voters: [],
+                      tie_break: [
+                        {
+                          seed: [],
+                          tied_candidates: [0n, 1n, 2n],
+                          stage: { RunoffCutoff: null },
+                          chosen: [0n, 1n, 2n],
+                          policy: { CommitteeDecision: null },
+                        },
+                      ],
+                      winner: [],
+                      voters_count: 0n,
+                      parent_id: [],
                       state: { Unresolved: null },
+                      weighted: false,
+                      delegated_votes: { "0": 0n, "1": 0n, "2": 0n },
+                      district_id: [],
                       votes_yes: { "0": 0n, "1": 0n, "2": 0n },
+                      district_votes: [],
+                      round: 1,
                       proposal_content:
                         createUserPropose.CreateUserPropose[
                           "PresidentialElections"
-                        ],
+                        ].candidates,
                     },
                     {
                       id: 1n,
                       creator: identityPrincipal,
-                      voters: [],
+                      tie_break: [],
+                      winner: [],
+                      voters_count: 0n,
+                      parent_id: [0n],
                       state: { Open: null },
-                      votes_yes: { "0": 0n, "1": 0n },
-                      proposal_content: [
-                        createUserPropose.CreateUserPropose[
-                          "PresidentialElections"
-                        ][0],
+                      weighted: false,
+                      delegated_votes: { "0": 0n, "1": 0n, "2": 0n },
+                      district_id: [],
+                      votes_yes: { "0": 0n, "1": 0n, "2": 0n },
+                      district_votes: [],
+                      round: 2,
+                      proposal_content:
                         createUserPropose.CreateUserPropose[
                           "PresidentialElections"
-                        ][1],
-                      ],
+                        ].candidates,
                     },
                   ],
                   null,
@@ -1248,28 +2740,48 @@ describe("Vote me", () => {
                     {
                       id: 0n,
                       creator: identityPrincipal,
-                      voters: [],
+                      tie_break: [
+                        {
+                          seed: [],
+                          tied_candidates: [0n, 1n, 2n],
+                          stage: { RunoffCutoff: null },
+                          chosen: [0n, 1n, 2n],
+                          policy: { CommitteeDecision: null },
+                        },
+                      ],
+                      winner: [],
+                      voters_count: 0n,
+                      parent_id: [],
                       state: { Unresolved: null },
+                      weighted: false,
+                      delegated_votes: { "0": 0n, "1": 0n, "2": 0n },
+                      district_id: [],
                       votes_yes: { "0": 0n, "1": 0n, "2": 0n },
+                      district_votes: [],
+                      round: 1,
                       proposal_content:
                         createUserPropose.CreateUserPropose[
                           "PresidentialElections"
-                        ],
+                        ].candidates,
                     },
                     {
                       id: 1n,
                       creator: identityPrincipal,
-                      voters: [],
+                      tie_break: [],
+                      winner: [],
+                      voters_count: 0n,
+                      parent_id: [0n],
                       state: { Open: null },
-                      votes_yes: { "0": 0n, "1": 0n },
-                      proposal_content: [
-                        createUserPropose.CreateUserPropose[
-                          "PresidentialElections"
-                        ][0],
+                      weighted: false,
+                      delegated_votes: { "0": 0n, "1": 0n, "2": 0n },
+                      district_id: [],
+                      votes_yes: { "0": 0n, "1": 0n, "2": 0n },
+                      district_votes: [],
+                      round: 2,
+                      proposal_content:
                         createUserPropose.CreateUserPropose[
                           "PresidentialElections"
-                        ][1],
-                      ],
+                        ].candidates,
                     },
                   ],
                   null,
@@ -1277,8 +2789,591 @@ describe("Vote me", () => {
                 )
               );
             });
+
+          await getVoteMeBackend(identitySecondUser)
+            .get_presidential_elections_history(1n)
+            .then(unwrap)
+            .then((_proposals: PresidentialElectionsProposeCandidType[]) => {
+              const rounds = _proposals.map(({ id, parent_id, round }) => ({
+                id,
+                parent_id,
+                round,
+              }));
+
+              expect(rounds).to.be.deep.eq([
+                { id: 0n, parent_id: [], round: 1 },
+                { id: 1n, parent_id: [0n], round: 2 },
+              ]);
+            });
+        });
+
+        it("User can vote on 'ballot' with several questions at once", async () => {
+          await activateUser();
+
+          const registerNewEntryIdentities = {
+            RegisterNewEntryIdentities: {
+              entry_identities: [entryIdentitySecondUserPrincipal],
+              activation_deadline: [],
+              district_id: [],
+            },
+          };
+          const registerPropose = await getVoteMeBackend(
+            identity
+          ).committee_create_propose(registerNewEntryIdentities).then(unwrap);
+          await getVoteMeBackend(identity).committee_vote_on_propose(
+            registerPropose
+          );
+          await sleep(
+            Number(config.committee_proposals_duration / 1_000n / 1_000n)
+          );
+          await activateUser(
+            entryIdentitySecondUser,
+            identitySecondUserPrincipal
+          );
+
+          const createUserPropose: CommitteeActions = {
+            CreateUserPropose: {
+              Ballot: {
+                questions: [
+                  { Referendum: "Should the town square be renovated?" },
+                  { CandidateRace: ["Jan Kowalski", "Mariusz Broda"] },
+                ],
+                weighted: false,
+              },
+            },
+          };
+          const proposeId = await getVoteMeBackend(
+            identity
+          ).committee_create_propose(createUserPropose).then(unwrap);
+          await getVoteMeBackend(identity).committee_vote_on_propose(
+            proposeId
+          );
+          await sleep(
+            Number(config.committee_proposals_duration / 1_000n / 1_000n)
+          );
+
+          await expect(
+            getVoteMeBackend(identitySecondUser).vote_on_propose(
+              { Ballot: [{ Referendum: true }] },
+              0n
+            )
+          ).to.eventually.be.deep.eq({
+            Err: { InvalidBallotAnswers: { expected: 2n, actual: 1n } },
+          });
+          await expect(
+            getVoteMeBackend(identitySecondUser).vote_on_propose(
+              { Ballot: [{ Referendum: true }, { CandidateRace: 5n }] },
+              0n
+            )
+          ).to.eventually.be.deep.eq({
+            Err: { InvalidBallotAnswer: { question: 1n } },
+          });
+
+          await expect(
+            getVoteMeBackend(identitySecondUser).vote_on_propose(
+              { Ballot: [{ Referendum: true }, { CandidateRace: 1n }] },
+              0n
+            )
+          ).to.eventually.be.deep.eq({ Ok: null });
+
+          await expect(
+            getVoteMeBackend(identitySecondUser).vote_on_propose(
+              { Ballot: [{ Referendum: false }, { CandidateRace: 0n }] },
+              0n
+            )
+          ).to.eventually.be.deep.eq({
+            Err: {
+              UserAlreadyVoted: { id: 0n, principal: identitySecondUserPrincipal },
+            },
+          });
+
+          await sleep(Number(config.user_proposals_duration / 1_000n / 1_000n));
+
+          await getVoteMeBackend(identitySecondUser)
+            .get_ballots()
+            .then((_proposals: BallotProposeCandidType[]) => {
+              const proposals = _proposals.map((propose) => {
+                const { created_at, votes, delegated_votes, ...rest } = propose;
+                return {
+                  ...rest,
+                  votes: votes.map((tally) => [...tally]),
+                  delegated_votes: delegated_votes.map((tally) => [...tally]),
+                };
+              });
+
+              expect(proposals).to.be.deep.eq([
+                {
+                  id: 0n,
+                  creator: identityPrincipal,
+                  questions:
+                    createUserPropose.CreateUserPropose["Ballot"].questions,
+                  voters_count: 1n,
+                  state: { Accepted: null },
+                  weighted: false,
+                  questions_state: [{ Accepted: null }, { Accepted: null }],
+                  votes: [
+                    [1n, 0n],
+                    [0n, 1n],
+                  ],
+                  delegated_votes: [
+                    [0n, 0n],
+                    [0n, 0n],
+                  ],
+                },
+              ]);
+            });
+        });
+
+        it("User vote on weighted 'ballot' is counted with user voting weight", async () => {
+          await activateUser();
+
+          const registerNewEntryIdentities = {
+            RegisterNewEntryIdentities: {
+              entry_identities: [entryIdentitySecondUserPrincipal],
+              activation_deadline: [],
+              district_id: [],
+            },
+          };
+          const registerPropose = await getVoteMeBackend(
+            identity
+          ).committee_create_propose(registerNewEntryIdentities).then(unwrap);
+          await getVoteMeBackend(identity).committee_vote_on_propose(
+            registerPropose
+          );
+          await sleep(
+            Number(config.committee_proposals_duration / 1_000n / 1_000n)
+          );
+          await activateUser(
+            entryIdentitySecondUser,
+            identitySecondUserPrincipal
+          );
+
+          const setVotingWeight: CommitteeActions = {
+            SetVotingWeight: [identitySecondUserPrincipal, 3n],
+          };
+          const createUserPropose: CommitteeActions = {
+            CreateUserPropose: {
+              Ballot: {
+                questions: [{ Referendum: "Should the roof be replaced?" }],
+                weighted: true,
+              },
+            },
+          };
+          for (const action of [setVotingWeight, createUserPropose]) {
+            const proposeId = await getVoteMeBackend(
+              identity
+            ).committee_create_propose(action).then(unwrap);
+            await getVoteMeBackend(identity).committee_vote_on_propose(
+              proposeId
+            );
+          }
+          await sleep(
+            Number(config.committee_proposals_duration / 1_000n / 1_000n)
+          );
+
+          await getVoteMeBackend(identity).vote_on_propose(
+            { Ballot: [{ Referendum: false }] },
+            0n
+          );
+          await getVoteMeBackend(identitySecondUser).vote_on_propose(
+            { Ballot: [{ Referendum: true }] },
+            0n
+          );
+
+          await sleep(Number(config.user_proposals_duration / 1_000n / 1_000n));
+
+          await getVoteMeBackend()
+            .get_ballots()
+            .then(([ballot]: BallotProposeCandidType[]) => {
+              expect(ballot.votes.map((tally) => [...tally])).to.be.deep.eq([
+                [3n, 1n],
+              ]);
+              expect(ballot.questions_state).to.be.deep.eq([
+                { Accepted: null },
+              ]);
+            });
+        });
+
+        it("Ballot with every referendum rejected is Rejected", async () => {
+          await activateUser();
+
+          const proposeId = await getVoteMeBackend(identity)
+            .committee_create_propose({
+              CreateUserPropose: {
+                Ballot: {
+                  questions: [
+                    { Referendum: "Should the roof be replaced?" },
+                    { Referendum: "Should the fence be painted?" },
+                  ],
+                  weighted: false,
+                },
+              },
+            })
+            .then(unwrap);
+          await getVoteMeBackend(identity)
+            .committee_vote_on_propose(proposeId)
+            .then(unwrap);
+          await sleep(
+            Number(config.committee_proposals_duration / 1_000n / 1_000n)
+          );
+
+          await getVoteMeBackend(identity)
+            .vote_on_propose(
+              { Ballot: [{ Referendum: false }, { Referendum: false }] },
+              0n
+            )
+            .then(unwrap);
+          await sleep(Number(config.user_proposals_duration / 1_000n / 1_000n));
+
+          await getVoteMeBackend()
+            .get_ballots()
+            .then(([ballot]: BallotProposeCandidType[]) => {
+              expect(ballot.questions_state).to.be.deep.eq([
+                { Rejected: null },
+                { Rejected: null },
+              ]);
+              expect(ballot.state).to.be.deep.eq({ Rejected: null });
+            });
+        });
+
+        it("User delegated vote is counted for the candidate of the delegate", async () => {
+          await activateUser();
+
+          const registerNewEntryIdentities = {
+            RegisterNewEntryIdentities: {
+              entry_identities: [entryIdentitySecondUserPrincipal],
+              activation_deadline: [],
+              district_id: [],
+            },
+          };
+          const registerPropose = await getVoteMeBackend(
+            identity
+          ).committee_create_propose(registerNewEntryIdentities).then(unwrap);
+          await getVoteMeBackend(identity).committee_vote_on_propose(
+            registerPropose
+          );
+          await sleep(
+            Number(config.committee_proposals_duration / 1_000n / 1_000n)
+          );
+          await activateUser(
+            entryIdentitySecondUser,
+            identitySecondUserPrincipal
+          );
+
+          await expect(
+            getVoteMeBackend(identitySecondUser).delegate_vote(
+              { All: null },
+              identityPrincipal
+            )
+          ).to.eventually.be.deep.eq({ Ok: null });
+          await expect(
+            getVoteMeBackend(identity).delegate_vote(
+              { PresidentialElections: null },
+              identitySecondUserPrincipal
+            )
+          ).to.eventually.be.deep.eq({
+            Err: { DelegationCycle: { delegate: identitySecondUserPrincipal } },
+          });
+
+          const createUserPropose: CommitteeActions = {
+            CreateUserPropose: {
+              PresidentialElections: {
+                candidates: ["Jan Kowalski", "Mariusz Broda"],
+                weighted: false,
+                district_id: [],
+              },
+            },
+          };
+          const proposeId = await getVoteMeBackend(
+            identity
+          ).committee_create_propose(createUserPropose).then(unwrap);
+          await getVoteMeBackend(identity).committee_vote_on_propose(
+            proposeId
+          );
+          await sleep(
+            Number(config.committee_proposals_duration / 1_000n / 1_000n)
+          );
+
+          await getVoteMeBackend(identity).vote_on_propose(
+            { PresidentialElections: 0n },
+            0n
+          );
+
+          await expect(
+            getVoteMeBackend().get_presidential_elections_delegation_graph(0n)
+          ).to.eventually.be.deep.eq({
+            Ok: [
+              {
+                delegator: identitySecondUserPrincipal,
+                delegate: identityPrincipal,
+                voted_directly: false,
+                representative: [identityPrincipal],
+              },
+            ],
+          });
+
+          await sleep(Number(config.user_proposals_duration / 1_000n / 1_000n));
+
+          await getVoteMeBackend()
+            .get_presidential_elections()
+            .then(([propose]: PresidentialElectionsProposeCandidType[]) => {
+              expect([...propose.votes_yes]).to.be.deep.eq([2n, 0n]);
+              expect([...propose.delegated_votes]).to.be.deep.eq([1n, 0n]);
+              expect(propose.winner).to.be.deep.eq([0n]);
+            });
+        });
+      });
+    });
+  });
+
+  describe("Tie broken by lot", () => {
+    it("Tied winner is drawn from the recorded seed", async () => {
+      const lotConfig: Config = { ...config, tie_break_policy: { Lot: null } };
+      deploy(lotConfig, [entryIdentityPrincipal]);
+
+      await activateUser();
+
+      const proposeId = await getVoteMeBackend(identity)
+        .committee_create_propose({
+          CreateUserPropose: {
+            PresidentialElections: {
+              candidates: ["Jan Kowalski", "Mariusz Broda"],
+              weighted: false,
+              district_id: [],
+            },
+          },
+        })
+        .then(unwrap);
+      await getVoteMeBackend(identity)
+        .committee_vote_on_propose(proposeId)
+        .then(unwrap);
+      await sleep(
+        Number(lotConfig.committee_proposals_duration / 1_000n / 1_000n)
+      );
+
+      // Nobody votes, both candidates tie and the lot is drawn once the vote closes
+      await sleep(
+        Number(lotConfig.committee_proposals_duration / 1_000n / 1_000n) + 2_000
+      );
+
+      const { state, winner, tie_break } = await getVoteMeBackend()
+        .get_presidential_election(0n)
+        .then(unwrap);
+      const [{ policy, tied_candidates, seed, chosen }] = tie_break;
+      expect(policy).to.be.deep.eq({ Lot: null });
+      expect(tied_candidates).to.be.deep.eq([0n, 1n]);
+      expect(seed).to.have.lengthOf(1);
+      expect(seed[0]).to.have.lengthOf(32);
+
+      // Every drawn seat takes 8 little endian bytes of the seed modulo the remaining pool
+      const roll = new DataView(Uint8Array.from(seed[0]).buffer).getBigUint64(
+        0,
+        true
+      );
+      const drawn =
+        tied_candidates[Number(roll % BigInt(tied_candidates.length))];
+      expect(chosen).to.be.deep.eq([drawn]);
+      expect(winner).to.be.deep.eq([drawn]);
+      expect(state).to.be.deep.eq({ Accepted: null });
+
+      const events = await getVoteMeBackend(identity)
+        .get_events(0n, 100n)
+        .then(unwrap);
+      expect(events.map((event: Event) => event.kind)).to.deep.include({
+        TieBroken: { id: 0n, chosen: [drawn] },
+      });
+    });
+  });
+
+  describe("Revoting allowed", () => {
+    const revotingConfig: Config = { ...config, allow_revoting: true };
+
+    beforeEach(async () => {
+      deploy(revotingConfig, [entryIdentityPrincipal]);
+
+      await activateUser();
+    });
+
+    const createUserPropose = async (userPropose: CommitteeActions) => {
+      const proposeId = await getVoteMeBackend(identity)
+        .committee_create_propose(userPropose)
+        .then(unwrap);
+      await getVoteMeBackend(identity)
+        .committee_vote_on_propose(proposeId)
+        .then(unwrap);
+      await sleep(
+        Number(revotingConfig.committee_proposals_duration / 1_000n / 1_000n)
+      );
+    };
+
+    it("Second vote on presidential elections replaces the first", async () => {
+      await createUserPropose({
+        CreateUserPropose: {
+          PresidentialElections: {
+            candidates: ["Jan Kowalski", "Mariusz Broda"],
+            weighted: false,
+            district_id: [],
+          },
+        },
+      });
+
+      await getVoteMeBackend(identity)
+        .vote_on_propose({ PresidentialElections: 0n }, 0n)
+        .then(unwrap);
+      await getVoteMeBackend()
+        .get_presidential_election(0n)
+        .then(unwrap)
+        .then(({ votes_yes, voters_count }) =>
+          expect({ votes_yes: [...votes_yes], voters_count }).to.be.deep.eq({
+            votes_yes: [1n, 0n],
+            voters_count: 1n,
+          })
+        );
+
+      await expect(
+        getVoteMeBackend(identity).vote_on_propose(
+          { PresidentialElections: 1n },
+          0n
+        )
+      ).to.eventually.be.deep.eq({ Ok: null });
+      await getVoteMeBackend()
+        .get_presidential_election(0n)
+        .then(unwrap)
+        .then(({ votes_yes, voters_count }) =>
+          expect({ votes_yes: [...votes_yes], voters_count }).to.be.deep.eq({
+            votes_yes: [0n, 1n],
+            voters_count: 1n,
+          })
+        );
+    });
+
+    it("Second ballot replaces the first on every question", async () => {
+      await createUserPropose({
+        CreateUserPropose: {
+          Ballot: {
+            questions: [
+              { Referendum: "Should the town square be renovated?" },
+              { CandidateRace: ["Jan Kowalski", "Mariusz Broda"] },
+            ],
+            weighted: false,
+          },
+        },
+      });
+
+      await getVoteMeBackend(identity)
+        .vote_on_propose(
+          { Ballot: [{ Referendum: true }, { CandidateRace: 1n }] },
+          0n
+        )
+        .then(unwrap);
+      await expect(
+        getVoteMeBackend(identity).vote_on_propose(
+          { Ballot: [{ Referendum: false }, { CandidateRace: 0n }] },
+          0n
+        )
+      ).to.eventually.be.deep.eq({ Ok: null });
+
+      await getVoteMeBackend()
+        .get_ballots()
+        .then(([propose]: BallotProposeCandidType[]) => {
+          expect(propose.votes.map((tally) => [...tally])).to.be.deep.eq([
+            [0n, 1n],
+            [1n, 0n],
+          ]);
+          expect(propose.voters_count).to.be.eq(1n);
         });
+    });
+
+    it("Committee member retracts their vote", async () => {
+      const proposeId = await getVoteMeBackend(identity)
+        .committee_create_propose({
+          CreateDistrict: { name: "Warszawa", parent_id: [] },
+        })
+        .then(unwrap);
+      await getVoteMeBackend(identity)
+        .committee_vote_on_propose(proposeId)
+        .then(unwrap);
+
+      await expect(
+        getVoteMeBackend(identity).committee_retract_vote(proposeId)
+      ).to.eventually.be.deep.eq({ Ok: null });
+      await getVoteMeBackend()
+        .get_committee_proposal(proposeId)
+        .then(unwrap)
+        .then(({ votes_yes, voters_count }) =>
+          expect({ votes_yes, voters_count }).to.be.deep.eq({
+            votes_yes: 0n,
+            voters_count: 0n,
+          })
+        );
+      await expect(
+        getVoteMeBackend(identity).committee_retract_vote(proposeId)
+      ).to.eventually.be.deep.eq({
+        Err: { UserNotVoted: { id: proposeId, principal: identityPrincipal } },
       });
+
+      await sleep(
+        Number(revotingConfig.committee_proposals_duration / 1_000n / 1_000n)
+      );
+      await getVoteMeBackend()
+        .get_committee_proposal(proposeId)
+        .then(unwrap)
+        .then(({ state }) => expect(state).to.be.deep.eq({ Rejected: null }));
+    });
+  });
+
+  describe("Canister upgrade", () => {
+    it("Proposals and config survive an upgrade and open proposals still close", async () => {
+      await activateUser();
+
+      const proposeId = await getVoteMeBackend(identity)
+        .committee_create_propose({
+          RegisterNewEntryIdentities: {
+            entry_identities: [entryIdentitySecondUserPrincipal],
+            activation_deadline: [],
+            district_id: [],
+          },
+        })
+        .then(unwrap);
+      await getVoteMeBackend(identity)
+        .committee_vote_on_propose(proposeId)
+        .then(unwrap);
+
+      deploy(config, [entryIdentityPrincipal], true, "upgrade");
+
+      await getVoteMeBackend()
+        .get_committee_proposal(proposeId)
+        .then(unwrap)
+        .then(({ votes_yes, voters_count }) =>
+          expect({ votes_yes, voters_count }).to.be.deep.eq({
+            votes_yes: 1n,
+            voters_count: 1n,
+          })
+        );
+      await expect(
+        getVoteMeBackend(identity).committee_create_propose({
+          CreateDistrict: { name: "Warszawa", parent_id: [] },
+        })
+      ).to.eventually.be.deep.eq({ Ok: 1n });
+
+      await sleep(
+        Number(config.committee_proposals_duration / 1_000n / 1_000n)
+      );
+
+      await getVoteMeBackend()
+        .get_committee_proposal(proposeId)
+        .then(unwrap)
+        .then(({ state }) => expect(state).to.be.deep.eq({ Accepted: null }));
+      const encryptedSeed2 = await aes_gcm_encrypt(
+        entryIdentitySecondUser.getKeyPair().publicKey,
+        user2seed
+      );
+      await expect(
+        getVoteMeBackend(entryIdentitySecondUser).activate_user(
+          identitySecondUserPrincipal,
+          encryptedSeed2
+        )
+      ).to.eventually.be.deep.eq({ Ok: null });
     });
   });
 });
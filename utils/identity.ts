@@ -1,5 +1,17 @@
-import { keccak256, toUtf8Bytes, getBytes } from "ethers";
+import { keccak256, sha256, toUtf8Bytes, getBytes, concat } from "ethers";
 import { Ed25519KeyIdentity } from "@dfinity/identity";
+import { Principal } from "@dfinity/principal";
+
+const OPAQUE_PRINCIPAL_CLASS = 0x01;
+
+// Mirrors `hash_entry_identity` of the canister, used to register entry identities when the config holds a salt
+export function hashEntryIdentity(salt: Uint8Array, entryIdentity: Principal) {
+  const hash = getBytes(sha256(concat([salt, entryIdentity.toUint8Array()])));
+
+  return Principal.fromUint8Array(
+    new Uint8Array([...hash.slice(0, 28), OPAQUE_PRINCIPAL_CLASS])
+  );
+}
 
 export function getEntryUserIdentity(
   pesel: string,
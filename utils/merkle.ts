@@ -0,0 +1,54 @@
+import { sha256, getBytes, concat, hexlify } from "ethers";
+import { Principal } from "@dfinity/principal";
+
+const MERKLE_LEAF_PREFIX = new Uint8Array([0x00]);
+const MERKLE_NODE_PREFIX = new Uint8Array([0x01]);
+
+// Mirrors `merkle_leaf` of the canister
+export function merkleLeaf(entryIdentity: Principal) {
+  return getBytes(
+    sha256(concat([MERKLE_LEAF_PREFIX, entryIdentity.toUint8Array()]))
+  );
+}
+
+// Pairs are hashed in sorted order, like `verify_inclusion` of the canister expects
+function merkleNode(left: Uint8Array, right: Uint8Array) {
+  const [first, second] =
+    hexlify(left) <= hexlify(right) ? [left, right] : [right, left];
+  return getBytes(sha256(concat([MERKLE_NODE_PREFIX, first, second])));
+}
+
+// Builds the voter roll tree, a node without a pair is carried to the next level as is
+export function buildVoterRoll(entryIdentities: Principal[]) {
+  const levels = [entryIdentities.map(merkleLeaf)];
+
+  while (levels[levels.length - 1].length > 1) {
+    const level = levels[levels.length - 1];
+    const next: Uint8Array[] = [];
+    for (let index = 0; index < level.length; index += 2) {
+      next.push(
+        index + 1 < level.length
+          ? merkleNode(level[index], level[index + 1])
+          : level[index]
+      );
+    }
+    levels.push(next);
+  }
+
+  const proof = (entryIdentity: Principal) => {
+    let index = entryIdentities.findIndex(
+      (principal) => principal.toText() === entryIdentity.toText()
+    );
+    const siblings: Uint8Array[] = [];
+    for (const level of levels.slice(0, -1)) {
+      const sibling = index % 2 === 0 ? index + 1 : index - 1;
+      if (sibling < level.length) {
+        siblings.push(level[sibling]);
+      }
+      index = Math.floor(index / 2);
+    }
+    return siblings;
+  };
+
+  return { root: levels[levels.length - 1][0], proof };
+}
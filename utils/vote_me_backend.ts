@@ -13,23 +13,53 @@ export const getVoteMeBackend = (identity?: Identity) => {
   });
 };
 
-export const deploy = (config: Config, entryIdentities: Principal[], silent = true) => {
+export const unwrap = <T, E>(result: { Ok: T } | { Err: E }): T => {
+  if ("Err" in result) {
+    throw new Error(JSON.stringify(result.Err));
+  }
+  return result.Ok;
+};
+
+export const deploy = (
+  config: Config,
+  entryIdentities: Principal[],
+  silent = true,
+  mode: "reinstall" | "upgrade" = "reinstall"
+) => {
   const principals = entryIdentities.map(
     (entryIdentity) => `principal "${entryIdentity}";\n`
   );
+  const salt = config.entry_identity_salt.map(
+    (salt) => `opt vec { ${[...salt].map((byte) => `${byte}:nat8`).join("; ")} }`
+  )[0];
+  const brackets = config.committee_size_brackets.map(
+    (bracket) =>
+      `record { max_population=${bracket.max_population}:nat64; committee_size=${bracket.committee_size}:nat64 };`
+  );
   const command = `
   dfx deploy vote_me_backend --argument '(
     record { 
       committee_threshold=${config.committee_threshold}:nat16; 
       max_committee_size=${config.max_committee_size}:nat64;
+      committee_size_brackets=vec { ${brackets.join(" ")} };
       committee_proposals_duration=${config.committee_proposals_duration}:nat64;
       user_proposals_duration=${config.user_proposals_duration}:nat64;
       presidential_elections_threshold=${config.presidential_elections_threshold}:nat16;
+      tie_break_policy=variant { ${Object.keys(config.tie_break_policy)[0]} };
+      runoff_rules=record {
+        max_rounds=${config.runoff_rules.max_rounds}:nat32;
+        expand_tied_runoff=${config.runoff_rules.expand_tied_runoff};
+      };
+      allow_revoting=${config.allow_revoting};
+      identity_reset_cooldown=${config.identity_reset_cooldown}:nat64;
+      entry_identity_salt=${salt ?? "null"};
+      candidate_registration_duration=${config.candidate_registration_duration}:nat64;
+      nomination_signatures_required=${config.nomination_signatures_required}:nat64;
     },
     vec {
           ${principals}
     }
-)' --mode reinstall --yes`;
+)' --mode ${mode} --yes`;
 
   execSync(command, { stdio: silent ? "ignore" : "inherit" });
 };